@@ -1,4 +1,8 @@
-use std::{collections::VecDeque, str::FromStr, string::ParseError};
+use std::{
+    collections::{HashMap, VecDeque},
+    str::FromStr,
+    string::ParseError,
+};
 
 #[derive(Debug)]
 enum InspectOp {
@@ -19,15 +23,7 @@ pub struct Monkey {
 
 impl Monkey {
     fn throw(&mut self) -> Option<(usize, u64)> {
-        if let Some(item) = self.items.pop_front() {
-            if item % self.throw_n as u64 == 0 {
-                Some((self.throw_true, item))
-            } else {
-                Some((self.throw_false, item))
-            }
-        } else {
-            None
-        }
+        self.items.pop_front().map(|item| (self.target(item), item))
     }
 
     fn inspect(&mut self, relief: u64) {
@@ -48,6 +44,98 @@ impl Monkey {
             *item /= amount;
         }
     }
+
+    /// Worry level of an item after this monkey inspects it, kept mod `relief`
+    /// (the LCM of every test) rather than divided down.
+    fn operate(&self, item: u64, relief: u64) -> u64 {
+        let item = match self.op {
+            InspectOp::Add(x) => item + x,
+            InspectOp::Mul(x) => item * x,
+            InspectOp::Squ => item.pow(2),
+        };
+
+        item % relief
+    }
+
+    /// Monkey this item is thrown to.
+    fn target(&self, item: u64) -> usize {
+        if item.is_multiple_of(self.throw_n as u64) {
+            self.throw_true
+        } else {
+            self.throw_false
+        }
+    }
+}
+
+/// Follows a single item through the monkeys.
+///
+/// Without the divide by 3, items never affect each other, and worry is kept
+/// below `relief` (the LCM of the tests), so each item only ever has a finite
+/// number of (holder, worry) states. That means the path is eventually a loop.
+#[derive(Debug)]
+struct ItemPath {
+    // counts[r][m] = times monkey m inspected this item in the first r rounds
+    counts: Vec<Vec<u64>>,
+    // Round the loop starts on, and how long it is.
+    start: usize,
+    length: usize,
+}
+
+impl ItemPath {
+    fn trace(monkeys: &[Monkey], holder: usize, item: u64, relief: u64) -> Self {
+        let mut state = (holder, item % relief);
+        let mut seen: HashMap<(usize, u64), usize> = HashMap::new();
+        let mut counts = vec![vec![0; monkeys.len()]];
+
+        loop {
+            if let Some(&start) = seen.get(&state) {
+                return ItemPath {
+                    length: counts.len() - 1 - start,
+                    counts,
+                    start,
+                };
+            }
+            seen.insert(state, counts.len() - 1);
+
+            // One round. A monkey later in the order gets the item again the
+            // same round, an earlier one has to wait for the next.
+            let mut round = counts[counts.len() - 1].clone();
+            let (mut holder, mut item) = state;
+            loop {
+                round[holder] += 1;
+                item = monkeys[holder].operate(item, relief);
+                let target = monkeys[holder].target(item);
+
+                if target <= holder {
+                    holder = target;
+                    break;
+                }
+                holder = target;
+            }
+
+            counts.push(round);
+            state = (holder, item);
+        }
+    }
+
+    /// Inspections of this item by each monkey after `rounds` rounds.
+    fn inspections(&self, rounds: u64) -> Vec<u64> {
+        if rounds < self.counts.len() as u64 {
+            return self.counts[rounds as usize].clone();
+        }
+
+        let loops = (rounds - self.start as u64) / self.length as u64;
+        let rest = (rounds - self.start as u64) % self.length as u64;
+
+        let first = &self.counts[self.start];
+        let last = &self.counts[self.start + self.length];
+
+        self.counts[self.start + rest as usize]
+            .iter()
+            .zip(first.iter().zip(last))
+            .map(|(n, (a, b))| n + loops * (b - a))
+            .collect()
+    }
 }
 
 impl FromStr for Monkey {
@@ -177,11 +265,31 @@ pub fn part_2(input: &str, rounds: usize, relief: u64) -> u64 {
     inspections[inspections.len() - 1] * inspections[inspections.len() - 2]
 }
 
+/// Same as part 2, but fast forwards through each item's loop instead of
+/// running every round, so `rounds` can be huge (like 10^12).
+pub fn part_2_cycles(input: &str, rounds: u64, relief: u64) -> u128 {
+    let monkeys = parse(input);
+    let mut inspections = vec![0u64; monkeys.len()];
+
+    for (holder, monkey) in monkeys.iter().enumerate() {
+        for &item in monkey.items.iter() {
+            let path = ItemPath::trace(&monkeys, holder, item, relief);
+
+            for (total, n) in inspections.iter_mut().zip(path.inspections(rounds)) {
+                *total += n;
+            }
+        }
+    }
+
+    inspections.sort();
+    inspections[inspections.len() - 1] as u128 * inspections[inspections.len() - 2] as u128
+}
+
 pub fn parse(input: &str) -> Vec<Monkey> {
-    let monkeys = input.split("\n\r\n");
+    let input = input.replace("\r\n", "\n");
+    let monkeys = input.split("\n\n");
 
     monkeys
-        .into_iter()
         .map(|monkey| monkey.parse::<Monkey>().unwrap())
         .collect()
 }
@@ -231,4 +339,25 @@ mod tests {
 
         println!("{:?}", part_2(&input, 10_000, MAGIC_BS))
     }
+
+    #[test]
+    fn test_part_2_cycles() {
+        let input = fs::read_to_string(TEST_INPUT).unwrap();
+
+        assert_eq!(part_2_cycles(&input, 10_000, TEST_MAGIC_BS), 2713310158);
+
+        let input = fs::read_to_string(INPUT).unwrap();
+
+        assert_eq!(
+            part_2_cycles(&input, 10_000, MAGIC_BS),
+            part_2(&input, 10_000, MAGIC_BS) as u128
+        )
+    }
+
+    #[test]
+    fn run_part_2_cycles() {
+        let input = fs::read_to_string(INPUT).unwrap();
+
+        println!("{:?}", part_2_cycles(&input, 1_000_000_000_000, MAGIC_BS))
+    }
 }
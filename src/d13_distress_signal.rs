@@ -1,87 +1,347 @@
-use std::{str::FromStr, string::ParseError};
+use std::{cmp::Ordering, fmt, slice, str::FromStr};
 
-#[derive(Debug)]
-struct ParseSignalError;
+use serde_json::Value;
+
+/// Why a packet failed to parse, and where.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseSignalError {
+    /// Byte offset into the line.
+    pub position: usize,
+    pub kind: ParseSignalErrorKind,
+}
 
 #[derive(Debug, Clone, PartialEq, Eq)]
-enum Packet {
+pub enum ParseSignalErrorKind {
+    UnexpectedEnd,
+    UnexpectedChar(char),
+    IntOverflow,
+    TrailingInput,
+}
+
+impl fmt::Display for ParseSignalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ParseSignalErrorKind::UnexpectedEnd => write!(f, "unexpected end at {}", self.position),
+            ParseSignalErrorKind::UnexpectedChar(ch) => {
+                write!(f, "unexpected '{}' at {}", ch, self.position)
+            }
+            ParseSignalErrorKind::IntOverflow => {
+                write!(f, "integer too large at {}", self.position)
+            }
+            ParseSignalErrorKind::TrailingInput => {
+                write!(f, "trailing input at {}", self.position)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseSignalError {}
+
+/// A packet is either an integer or a list of packets.
+///
+/// Displays in the same format it parses from, so `to_string` round trips.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Packet {
     List(Vec<Packet>),
     Int(u32),
 }
 
+/// Builds a [`Packet`](crate::d13_distress_signal::Packet) from list syntax,
+/// e.g. `packet!([1, [2, 3], []])`.
+#[macro_export]
+macro_rules! packet {
+    ([$($inner:tt),* $(,)?]) => {
+        $crate::d13_distress_signal::Packet::List(vec![$($crate::packet!($inner)),*])
+    };
+    ($n:expr) => {
+        $crate::d13_distress_signal::Packet::Int($n)
+    };
+}
+
+/// Recursive descent over the bytes of a single packet.
+struct PacketParser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> PacketParser<'a> {
+    fn new(s: &'a str) -> Self {
+        PacketParser {
+            bytes: s.as_bytes(),
+            position: 0,
+        }
+    }
+
+    fn error(&self, kind: ParseSignalErrorKind) -> ParseSignalError {
+        ParseSignalError {
+            position: self.position,
+            kind,
+        }
+    }
+
+    fn unexpected(&self) -> ParseSignalError {
+        match self.peek() {
+            Some(ch) => self.error(ParseSignalErrorKind::UnexpectedChar(ch as char)),
+            None => self.error(ParseSignalErrorKind::UnexpectedEnd),
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.position).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|ch| ch.is_ascii_whitespace()) {
+            self.position += 1;
+        }
+    }
+
+    fn packet(&mut self) -> Result<Packet, ParseSignalError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'[') => self.list(),
+            Some(b'0'..=b'9') => self.int(),
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn list(&mut self) -> Result<Packet, ParseSignalError> {
+        let mut list = Vec::new();
+        self.position += 1; // [
+
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.position += 1;
+            return Ok(Packet::List(list));
+        }
+
+        loop {
+            list.push(self.packet()?);
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b']') => {
+                    self.position += 1;
+                    return Ok(Packet::List(list));
+                }
+                _ => return Err(self.unexpected()),
+            }
+        }
+    }
+
+    fn int(&mut self) -> Result<Packet, ParseSignalError> {
+        let start = self.position;
+        let mut n: u32 = 0;
+
+        while let Some(digit @ b'0'..=b'9') = self.peek() {
+            n = n
+                .checked_mul(10)
+                .and_then(|n| n.checked_add((digit - b'0') as u32))
+                .ok_or(ParseSignalError {
+                    position: start,
+                    kind: ParseSignalErrorKind::IntOverflow,
+                })?;
+            self.position += 1;
+        }
+
+        Ok(Packet::Int(n))
+    }
+
+    fn finish(&mut self) -> Result<(), ParseSignalError> {
+        self.skip_whitespace();
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => Err(self.error(ParseSignalErrorKind::TrailingInput)),
+        }
+    }
+}
+
 impl FromStr for Packet {
     type Err = ParseSignalError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut packet_stack = Vec::new();
-        let ch_iter = s.chars();
-        let mut pay = String::new();
+        let mut parser = PacketParser::new(s);
+        let packet = parser.packet()?;
+        parser.finish()?;
 
-        for ch in ch_iter {
-            match ch {
-                '[' => packet_stack.push(Packet::List(Vec::new())),
-                ']' => {
-                    if !pay.is_empty() {
-                        let parent = packet_stack.pop().ok_or(ParseSignalError)?;
-                        if let Packet::List(mut par) = parent {
-                            par.push(Packet::Int(pay.parse().unwrap()));
-                            packet_stack.push(Packet::List(par));
-                            pay.clear();
-                        } else {
-                            panic!("Non-List Signal in stack after ','!");
-                        }
-                    }
+        Ok(packet)
+    }
+}
 
-                    let elem = packet_stack.pop().ok_or(ParseSignalError)?;
-                    if let Some(parent) = packet_stack.pop() {
-                        if let Packet::List(mut par) = parent {
-                            par.push(elem);
-                            packet_stack.push(Packet::List(par));
-                        } else {
-                            panic!("Non-List Signal in stack after ']'!");
-                        }
-                    } else {
-                        return Ok(elem);
+impl fmt::Display for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Packet::Int(n) => write!(f, "{}", n),
+            Packet::List(list) => {
+                write!(f, "[")?;
+                for (i, packet) in list.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
                     }
+                    write!(f, "{}", packet)?;
                 }
-                ',' => {
-                    if !pay.is_empty() {
-                        let parent = packet_stack.pop().ok_or(ParseSignalError)?;
-                        if let Packet::List(mut par) = parent {
-                            par.push(Packet::Int(pay.parse().unwrap()));
-                            packet_stack.push(Packet::List(par));
-                            pay.clear();
-                        } else {
-                            panic!("Non-List Signal in stack after ','!");
-                        }
-                    }
-                }
-                _ => pay.push(ch),
+                write!(f, "]")
             }
         }
+    }
+}
+
+impl From<&Packet> for Value {
+    fn from(packet: &Packet) -> Self {
+        match packet {
+            Packet::Int(n) => Value::from(*n),
+            Packet::List(list) => Value::Array(list.iter().map(Value::from).collect()),
+        }
+    }
+}
+
+impl From<Packet> for Value {
+    fn from(packet: Packet) -> Self {
+        Value::from(&packet)
+    }
+}
+
+/// A JSON value that isn't made of only arrays and `u32`s.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotAPacket(pub Value);
+
+impl TryFrom<&Value> for Packet {
+    type Error = NotAPacket;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Array(list) => Ok(Packet::List(
+                list.iter()
+                    .map(Packet::try_from)
+                    .collect::<Result<_, _>>()?,
+            )),
+            Value::Number(n) => n
+                .as_u64()
+                .and_then(|n| u32::try_from(n).ok())
+                .map(Packet::Int)
+                .ok_or_else(|| NotAPacket(value.clone())),
+            _ => Err(NotAPacket(value.clone())),
+        }
+    }
+}
 
-        panic!("Unclosed bracket detected!");
+impl TryFrom<Value> for Packet {
+    type Error = NotAPacket;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        Packet::try_from(&value)
     }
 }
 
 impl PartialOrd for Packet {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
 impl Ord for Packet {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+    // An int compared against a list is treated as a list of one, which is
+    // just a one element slice over the int itself. No allocation needed.
+    fn cmp(&self, other: &Self) -> Ordering {
         match (self, other) {
             (Packet::Int(x), Packet::Int(y)) => x.cmp(y),
-            (Packet::List(x), Packet::List(y)) => x.cmp(y),
-            (Packet::List(x), Packet::Int(y)) => {
-                Packet::List(x.clone()).cmp(&Packet::List(vec![Packet::Int(*y)]))
-            }
-            (Packet::Int(x), Packet::List(y)) => {
-                Packet::List(vec![Packet::Int(*x)]).cmp(&Packet::List(y.clone()))
+            (Packet::List(x), Packet::List(y)) => x.as_slice().cmp(y.as_slice()),
+            (Packet::List(x), Packet::Int(_)) => x.as_slice().cmp(slice::from_ref(other)),
+            (Packet::Int(_), Packet::List(y)) => slice::from_ref(self).cmp(y.as_slice()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    Open,
+    Close,
+    Int(u32),
+}
+
+/// Streams the tokens of a packet's text, with room to fake the brackets of
+/// an int that's been promoted to a list.
+struct Tokens<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    promoted: Option<u32>,
+    closes: usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(s: &'a str) -> Self {
+        Tokens {
+            bytes: s.as_bytes(),
+            position: 0,
+            promoted: None,
+            closes: 0,
+        }
+    }
+
+    /// `n` was just read, but needs to be `[n]`. The caller has already
+    /// matched the `[`, so replay `n` then add the `]`.
+    fn promote(&mut self, n: u32) {
+        self.promoted = Some(n);
+        self.closes += 1;
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        if let Some(n) = self.promoted.take() {
+            return Some(Token::Int(n));
+        }
+        if self.closes > 0 {
+            self.closes -= 1;
+            return Some(Token::Close);
+        }
+
+        while let Some(&ch) = self.bytes.get(self.position) {
+            self.position += 1;
+            match ch {
+                b'[' => return Some(Token::Open),
+                b']' => return Some(Token::Close),
+                b'0'..=b'9' => {
+                    let mut n = (ch - b'0') as u32;
+                    while let Some(&digit @ b'0'..=b'9') = self.bytes.get(self.position) {
+                        n = n * 10 + (digit - b'0') as u32;
+                        self.position += 1;
+                    }
+                    return Some(Token::Int(n));
+                }
+                _ => (), // Commas and whitespace
             }
         }
+
+        None
+    }
+}
+
+/// Compares two packets straight from their text, without building either.
+///
+/// Expects well formed packets; anything that isn't a bracket or a digit is
+/// skipped.
+pub fn compare_str(left: &str, right: &str) -> Ordering {
+    let mut left = Tokens::new(left);
+    let mut right = Tokens::new(right);
+
+    loop {
+        match (left.next(), right.next()) {
+            (Some(Token::Int(x)), Some(Token::Int(y))) if x != y => return x.cmp(&y),
+            (Some(Token::Close), Some(Token::Close))
+            | (Some(Token::Open), Some(Token::Open))
+            | (Some(Token::Int(_)), Some(Token::Int(_))) => (),
+            (Some(Token::Close), Some(_)) => return Ordering::Less,
+            (Some(_), Some(Token::Close)) => return Ordering::Greater,
+            (Some(Token::Int(x)), Some(Token::Open)) => left.promote(x),
+            (Some(Token::Open), Some(Token::Int(y))) => right.promote(y),
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+        }
     }
 }
 
@@ -92,20 +352,144 @@ pub struct Signal {
 }
 
 impl FromStr for Signal {
-    type Err = ParseError;
+    type Err = ParseSignalError;
 
     // s is a single pair of lines.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let missing = ParseSignalError {
+            position: 0,
+            kind: ParseSignalErrorKind::UnexpectedEnd,
+        };
         let mut lines = s.lines();
         let (left, right) = (
-            lines.next().unwrap().parse().unwrap(),
-            lines.next().unwrap().parse().unwrap(),
+            lines.next().ok_or(missing.clone())?.parse()?,
+            lines.next().ok_or(missing)?.parse()?,
         );
 
         Ok(Signal { left, right })
     }
 }
 
+impl Signal {
+    /// Walks through why the pair is or isn't in the right order.
+    pub fn explain(&self) -> Trace {
+        explain(&self.left, &self.right)
+    }
+}
+
+/// One line of an explained comparison.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceStep {
+    pub depth: usize,
+    pub message: String,
+}
+
+/// The decision path of a comparison, in the same words the puzzle uses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trace {
+    pub ordering: Ordering,
+    pub steps: Vec<TraceStep>,
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for step in self.steps.iter() {
+            writeln!(f, "{}- {}", "  ".repeat(step.depth), step.message)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Compares two packets, recording every step taken.
+pub fn explain(left: &Packet, right: &Packet) -> Trace {
+    let mut steps = Vec::new();
+    let ordering = explain_step(left, right, 0, &mut steps);
+
+    Trace { ordering, steps }
+}
+
+fn explain_step(
+    left: &Packet,
+    right: &Packet,
+    depth: usize,
+    steps: &mut Vec<TraceStep>,
+) -> Ordering {
+    note(steps, depth, format!("Compare {} vs {}", left, right));
+
+    match (left, right) {
+        (Packet::Int(x), Packet::Int(y)) => {
+            match x.cmp(y) {
+                Ordering::Less => note(
+                    steps,
+                    depth + 1,
+                    "Left side is smaller, so inputs are in the right order".to_string(),
+                ),
+                Ordering::Greater => note(
+                    steps,
+                    depth + 1,
+                    "Right side is smaller, so inputs are not in the right order".to_string(),
+                ),
+                Ordering::Equal => (),
+            }
+            x.cmp(y)
+        }
+        (Packet::List(x), Packet::List(y)) => {
+            for (l, r) in x.iter().zip(y.iter()) {
+                let ordering = explain_step(l, r, depth + 1, steps);
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+
+            match x.len().cmp(&y.len()) {
+                Ordering::Less => note(
+                    steps,
+                    depth + 1,
+                    "Left side ran out of items, so inputs are in the right order".to_string(),
+                ),
+                Ordering::Greater => note(
+                    steps,
+                    depth + 1,
+                    "Right side ran out of items, so inputs are not in the right order".to_string(),
+                ),
+                Ordering::Equal => (),
+            }
+            x.len().cmp(&y.len())
+        }
+        (Packet::Int(x), Packet::List(_)) => {
+            note(
+                steps,
+                depth + 1,
+                format!("Mixed types; convert left to [{}] and retry comparison", x),
+            );
+            explain_step(&Packet::List(vec![left.clone()]), right, depth + 1, steps)
+        }
+        (Packet::List(_), Packet::Int(y)) => {
+            note(
+                steps,
+                depth + 1,
+                format!("Mixed types; convert right to [{}] and retry comparison", y),
+            );
+            explain_step(left, &Packet::List(vec![right.clone()]), depth + 1, steps)
+        }
+    }
+}
+
+fn note(steps: &mut Vec<TraceStep>, depth: usize, message: String) {
+    steps.push(TraceStep { depth, message });
+}
+
+/// Every pair's explanation, headed like the puzzle's walkthrough.
+pub fn report(input: &str) -> String {
+    parse(input)
+        .iter()
+        .enumerate()
+        .map(|(i, signal)| format!("== Pair {} ==\n{}", i + 1, signal.explain()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 pub fn part_1(input: &str) -> usize {
     let signals = parse(input);
 
@@ -120,31 +504,40 @@ pub fn part_1(input: &str) -> usize {
 }
 
 pub fn part_2(input: &str) -> usize {
-    let mut signals: Vec<Packet> = input
-        .split("\n\r\n")
-        .flat_map(|pair| pair.lines())
-        .map(|line| line.parse().unwrap())
-        .collect();
+    decoder_key(&packets(input), &[packet!([[2]]), packet!([[6]])])
+}
 
-    signals.push("[[2]]".parse().unwrap());
-    signals.push("[[6]]".parse().unwrap());
+/// 1 based index each divider would end up at if it was added to the packets
+/// and everything was sorted. Nothing is sorted; each divider just counts
+/// the packets (and other dividers) that come before it.
+pub fn divider_positions(packets: &[Packet], dividers: &[Packet]) -> Vec<usize> {
+    dividers
+        .iter()
+        .map(|divider| {
+            packets.iter().filter(|packet| *packet < divider).count()
+                + dividers.iter().filter(|other| *other < divider).count()
+                + 1
+        })
+        .collect()
+}
 
-    signals.sort();
+/// Product of the divider positions.
+pub fn decoder_key(packets: &[Packet], dividers: &[Packet]) -> usize {
+    divider_positions(packets, dividers).iter().product()
+}
 
-    (signals
-        .iter()
-        .position(|x| x == &"[[2]]".parse::<Packet>().unwrap())
-        .unwrap()
-        + 1)
-        * (signals
-            .iter()
-            .position(|x| x == &"[[6]]".parse::<Packet>().unwrap())
-            .unwrap()
-            + 1)
+/// Every packet in the input, ignoring the pairing.
+pub fn packets(input: &str) -> Vec<Packet> {
+    input
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.parse().unwrap())
+        .collect()
 }
 
 pub fn parse(input: &str) -> Vec<Signal> {
-    let signals = input.split("\n\r\n");
+    let input = input.replace("\r\n", "\n");
+    let signals = input.split("\n\n");
 
     signals.map(|pair| pair.parse().unwrap()).collect()
 }
@@ -191,4 +584,120 @@ mod tests {
 
         println!("{:?}", part_2(&input))
     }
+
+    #[test]
+    fn check_packet_round_trip() {
+        let input = fs::read_to_string(INPUT).unwrap();
+
+        for line in input.lines().filter(|line| !line.is_empty()) {
+            let packet: Packet = line.parse().unwrap();
+            assert_eq!(packet.to_string(), line);
+
+            let json: Value = serde_json::from_str(line).unwrap();
+            assert_eq!(Value::from(&packet), json);
+            assert_eq!(Packet::try_from(json).unwrap(), packet);
+        }
+
+        assert_eq!(
+            "[[1],[2,3,4],[]]".parse::<Packet>().unwrap(),
+            packet!([[1], [2, 3, 4], []])
+        );
+    }
+
+    #[test]
+    fn check_compare_str() {
+        let input = fs::read_to_string(INPUT).unwrap();
+        let mut lines: Vec<&str> = input.lines().filter(|line| !line.is_empty()).collect();
+
+        for pair in lines.chunks(2) {
+            let (left, right): (Packet, Packet) =
+                (pair[0].parse().unwrap(), pair[1].parse().unwrap());
+            assert_eq!(compare_str(pair[0], pair[1]), left.cmp(&right));
+            assert_eq!(compare_str(pair[1], pair[0]), right.cmp(&left));
+        }
+
+        let mut packets: Vec<Packet> = lines.iter().map(|line| line.parse().unwrap()).collect();
+        packets.sort();
+        lines.sort_by(|a, b| compare_str(a, b));
+
+        assert_eq!(
+            lines,
+            packets.iter().map(|p| p.to_string()).collect::<Vec<_>>()
+        );
+
+        assert_eq!(compare_str("[[1],[2,3,4]]", "[[1],4]"), Ordering::Less);
+        assert_eq!(compare_str("[[[]]]", "[[]]"), Ordering::Greater);
+        assert_eq!(compare_str("[3]", "[[[[3]]]]"), Ordering::Equal);
+    }
+
+    #[test]
+    fn check_explain() {
+        let input = fs::read_to_string(TEST_INPUT).unwrap();
+        let report = report(&input);
+
+        assert!(report.contains(
+            "== Pair 2 ==
+- Compare [[1],[2,3,4]] vs [[1],4]
+  - Compare [1] vs [1]
+    - Compare 1 vs 1
+  - Compare [2,3,4] vs 4
+    - Mixed types; convert right to [4] and retry comparison
+    - Compare [2,3,4] vs [4]
+      - Compare 2 vs 4
+        - Left side is smaller, so inputs are in the right order
+"
+        ));
+        assert!(report.contains(
+            "== Pair 7 ==
+- Compare [[[]]] vs [[]]
+  - Compare [[]] vs []
+    - Right side ran out of items, so inputs are not in the right order
+"
+        ));
+
+        for signal in parse(&input) {
+            assert_eq!(signal.explain().ordering, signal.left.cmp(&signal.right));
+        }
+    }
+
+    #[test]
+    fn check_divider_positions() {
+        let input = fs::read_to_string(TEST_INPUT).unwrap();
+        let packets = packets(&input);
+
+        assert_eq!(
+            divider_positions(&packets, &[packet!([[2]]), packet!([[6]])]),
+            vec![10, 14]
+        );
+        assert_eq!(
+            divider_positions(&packets, &[packet!([[6]]), packet!([]), packet!([[2]])]),
+            vec![15, 1, 11]
+        );
+        assert_eq!(
+            decoder_key(&packets, &[packet!([[2]]), packet!([[6]])]),
+            140
+        );
+    }
+
+    #[test]
+    fn check_packet_errors() {
+        let error = |position, kind| Err(ParseSignalError { position, kind });
+
+        assert_eq!(
+            "[1,2".parse::<Packet>(),
+            error(4, ParseSignalErrorKind::UnexpectedEnd)
+        );
+        assert_eq!(
+            "[1,,2]".parse::<Packet>(),
+            error(3, ParseSignalErrorKind::UnexpectedChar(','))
+        );
+        assert_eq!(
+            "[1]]".parse::<Packet>(),
+            error(3, ParseSignalErrorKind::TrailingInput)
+        );
+        assert_eq!(
+            "[99999999999]".parse::<Packet>(),
+            error(1, ParseSignalErrorKind::IntOverflow)
+        );
+    }
 }
@@ -1,18 +1,86 @@
 use std::{
-    collections::{BTreeMap, BTreeSet},
+    collections::BTreeMap,
     fmt::{Display, Error},
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
     str::FromStr,
 };
 
 use itertools::Itertools;
 
 #[derive(Debug)]
-struct ParseCaveError;
+pub struct ParseCaveError;
 
+/// Fixed size grid of on/off cells, a bit per cell.
+#[derive(Debug, Clone)]
+struct Bitmap {
+    width: usize,
+    bits: Vec<u64>,
+}
+
+impl Bitmap {
+    fn new(width: usize, height: usize) -> Self {
+        Bitmap {
+            width,
+            bits: vec![0; (width * height).div_ceil(64)],
+        }
+    }
+
+    fn get(&self, x: usize, y: usize) -> bool {
+        let i = y * self.width + x;
+        self.bits[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    fn set(&mut self, x: usize, y: usize) {
+        let i = y * self.width + x;
+        self.bits[i / 64] |= 1 << (i % 64);
+    }
+}
+
+/// Dense slice of the cave, covering the columns `left..left + width` and the
+/// rows `0..height`. Anything outside of that is open air that sand falls
+/// through forever, unless there's a floor.
 #[derive(Debug)]
-struct Cave {
-    rocks: BTreeMap<usize, BTreeSet<usize>>, //column, row
-    sand: BTreeSet<(usize, usize)>,
+pub struct Cave {
+    left: usize,
+    width: usize,
+    height: usize,
+    rock: Bitmap,
+    sand: Bitmap,
+    // Row of the endless floor. It isn't stored in `rock`; the slice just
+    // grows sideways whenever sand spreads past it.
+    floor: Option<usize>,
+    sources: Vec<(usize, usize)>,
+    // Where the last grain from each source fell through. Every spot on it
+    // except the last is still open, so the next grain can pick up from
+    // there instead of starting back at the source.
+    paths: BTreeMap<(usize, usize), Vec<(usize, usize)>>,
+}
+
+/// What stops [`Cave::pour`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    /// A source is filled in.
+    Blocked,
+    /// A grain falls out of the cave.
+    Void,
+    /// This many grains have settled, across all sources.
+    Grains(usize),
+}
+
+/// Grains settled from each source, in the same order as the sources, and
+/// why the pouring stopped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pour {
+    pub grains: Vec<usize>,
+    pub stop: Stop,
+}
+
+impl Pour {
+    pub fn total(&self) -> usize {
+        self.grains.iter().sum()
+    }
 }
 
 impl FromStr for Cave {
@@ -25,48 +93,34 @@ impl FromStr for Cave {
                     .map(|tuple| {
                         tuple
                             .split(',')
-                            .map(|t| t.parse().unwrap())
+                            .map(|t| t.trim().parse().map_err(|_| ParseCaveError))
+                            .collect::<Result<Vec<usize>, _>>()?
+                            .into_iter()
                             .collect_tuple()
-                            .unwrap()
+                            .ok_or(ParseCaveError)
                     })
                     .collect()
             })
-            .collect();
+            .collect::<Result<_, _>>()?;
+
+        let points = || endpoints.iter().flatten();
+        let left = points().map(|p| p.0).min().ok_or(ParseCaveError)?;
+        let right = points().map(|p| p.0).max().ok_or(ParseCaveError)?;
+        let bottom = points().map(|p| p.1).max().ok_or(ParseCaveError)?;
 
-        let mut rocks: BTreeMap<usize, BTreeSet<usize>> = BTreeMap::new();
+        let mut cave = Cave::new(left, right - left + 1, bottom + 1);
 
         for rock_ends in endpoints.iter() {
             for eps in rock_ends.windows(2) {
                 if eps[0].0 == eps[1].0 {
-                    let mut sorted = [eps[0].1, eps[1].1];
-                    sorted.sort();
-                    let start = sorted[0];
-                    let end = sorted[1];
-
-                    // Can append, but not really worth bothering for now.
+                    let (start, end) = (eps[0].1.min(eps[1].1), eps[0].1.max(eps[1].1));
                     for row in start..=end {
-                        rocks
-                            .entry(eps[0].0)
-                            .and_modify(|r| {
-                                r.insert(row);
-                            })
-                            .or_insert_with(|| BTreeSet::from([row]));
+                        cave.add_rock((eps[0].0, row));
                     }
                 } else if eps[0].1 == eps[1].1 {
-                    let mut sorted = [eps[0].0, eps[1].0];
-                    sorted.sort();
-                    let start = sorted[0];
-                    let end = sorted[1];
-
-                    let mut dir = [start, end];
-                    dir.sort();
-                    for column in dir[0]..=dir[1] {
-                        rocks
-                            .entry(column)
-                            .and_modify(|r| {
-                                r.insert(eps[0].1);
-                            })
-                            .or_insert_with(|| BTreeSet::from([eps[0].1]));
+                    let (start, end) = (eps[0].0.min(eps[1].0), eps[0].0.max(eps[1].0));
+                    for column in start..=end {
+                        cave.add_rock((column, eps[0].1));
                     }
                 } else {
                     panic!("Diagonal rock?");
@@ -74,35 +128,20 @@ impl FromStr for Cave {
             }
         }
 
-        Ok(Cave {
-            rocks,
-            sand: BTreeSet::new(),
-        })
+        Ok(cave)
     }
 }
 
 impl Display for Cave {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let first_column = self.rocks.keys().next().unwrap();
-        let width = self.rocks.keys().last().unwrap() - self.rocks.keys().next().unwrap();
-        let height = self
-            .rocks
-            .iter()
-            .map(|(_, column)| column.iter().max())
-            .max()
-            .unwrap()
-            .unwrap();
-
         let mut output = String::new();
-        for row in 0..=*height {
-            for column in *first_column..=(first_column + width) {
-                if (column, row) == (500, 0) {
+        for row in 0..self.rows() {
+            for column in self.left..self.left + self.width {
+                if self.sources.contains(&(column, row)) {
                     output.push('+');
-                } else if self.sand.contains(&(column, row)) {
+                } else if self.is_sand((column, row)) {
                     output.push('o');
-                } else if self.rocks.contains_key(&column)
-                    && self.rocks.get(&column).unwrap().contains(&row)
-                {
+                } else if self.is_rock((column, row)) {
                     output.push('#');
                 } else {
                     output.push('.');
@@ -116,107 +155,337 @@ impl Display for Cave {
 }
 
 impl Cave {
-    fn add_floor(&mut self, past_bottom: usize, left: usize, right: usize) {
-        let height = self
-            .rocks
-            .iter()
-            .map(|(_, column)| column.iter().max())
-            .max()
-            .unwrap()
-            .unwrap()
-            + past_bottom;
-
-        // Can append, but not really worth bothering for now.
-        for column in left..=right {
-            self.rocks
-                .entry(column)
-                .and_modify(|r| {
-                    r.insert(height);
-                })
-                .or_insert_with(|| BTreeSet::from([height]));
+    fn new(left: usize, width: usize, height: usize) -> Self {
+        Cave {
+            left,
+            width,
+            height,
+            rock: Bitmap::new(width, height),
+            sand: Bitmap::new(width, height),
+            floor: None,
+            sources: vec![(500, 0)],
+            paths: BTreeMap::new(),
+        }
+    }
+
+    /// Position in the bitmaps, if it's inside the cave.
+    fn cell(&self, pos: (usize, usize)) -> Option<(usize, usize)> {
+        if pos.0 >= self.left && pos.0 < self.left + self.width && pos.1 < self.height {
+            Some((pos.0 - self.left, pos.1))
+        } else {
+            None
         }
     }
 
+    /// Rows to draw, counting the floor.
+    fn rows(&self) -> usize {
+        self.floor.map_or(self.height, |floor| floor + 1)
+    }
+
+    fn is_rock(&self, pos: (usize, usize)) -> bool {
+        Some(pos.1) == self.floor || self.cell(pos).is_some_and(|(x, y)| self.rock.get(x, y))
+    }
+
+    fn is_sand(&self, pos: (usize, usize)) -> bool {
+        self.cell(pos).is_some_and(|(x, y)| self.sand.get(x, y))
+    }
+
+    fn is_blocked(&self, pos: (usize, usize)) -> bool {
+        self.is_rock(pos) || self.is_sand(pos)
+    }
+
+    fn add_rock(&mut self, pos: (usize, usize)) {
+        let (x, y) = self.cell(pos).expect("Rock outside of cave!");
+        self.rock.set(x, y);
+    }
+
+    /// Grows the cave to cover `left..left + width` and `0..height`, keeping
+    /// everything already in it.
+    fn resize(&mut self, left: usize, width: usize, height: usize) {
+        let mut resized = Cave::new(left, width, height);
+
+        for row in 0..self.height {
+            for column in self.left..self.left + self.width {
+                if let Some((x, y)) = resized.cell((column, row)) {
+                    if self.is_rock((column, row)) {
+                        resized.rock.set(x, y);
+                    }
+                    if self.is_sand((column, row)) {
+                        resized.sand.set(x, y);
+                    }
+                }
+            }
+        }
+
+        resized.floor = self.floor;
+        resized.sources = std::mem::take(&mut self.sources);
+        resized.paths = std::mem::take(&mut self.paths);
+        *self = resized;
+    }
+
+    /// Makes sure `column` is inside the slice. Grows by at least the height
+    /// of the cave, which is as far as sand can spread from one spot.
+    fn cover(&mut self, column: usize) {
+        if column < self.left {
+            let left = column.saturating_sub(self.height);
+            self.resize(left, self.left + self.width - left, self.height);
+        } else if column >= self.left + self.width {
+            let right = column + self.height;
+            self.resize(self.left, right - self.left + 1, self.height);
+        }
+    }
+
+    /// Adds an endless floor `past_bottom` rows under the lowest rock.
+    pub fn add_floor(&mut self, past_bottom: usize) {
+        let floor = self.height - 1 + past_bottom;
+        self.resize(self.left, self.width, floor);
+        self.floor = Some(floor);
+    }
+
+    /// Where sand pours in from. Just `500,0` unless it's changed.
+    pub fn sources(&self) -> &[(usize, usize)] {
+        &self.sources
+    }
+
+    pub fn set_sources(&mut self, sources: &[(usize, usize)]) {
+        self.sources = sources.to_vec();
+    }
+
+    /// Drops one grain from `start`. Returns where it came to rest, or `None`
+    /// if it fell out of the cave (or `start` is already filled).
     fn add_sand(&mut self, start: (usize, usize)) -> Option<(usize, usize)> {
-        let mut sand_pos = start;
-
-        while let Some(column) = self.rocks.get(&sand_pos.0) {
-            // while there are rocks in this column,
-            if let Some(ground) = column.range(sand_pos.1..).next() {
-                // move the sand to the nearest ground
-                if ground - 1 > sand_pos.1 {
-                    sand_pos.1 = ground - 1;
-                } else if self
-                    // If down-left is open, move there
-                    .rocks
-                    .get(&(sand_pos.0 - 1))?
-                    .range(sand_pos.1 + 1..)
-                    .next()?
-                    != &(sand_pos.1 + 1)
-                {
-                    sand_pos.0 -= 1;
-                    sand_pos.1 += 1;
-                } else if self
-                    // else, if down-right is open, move there
-                    .rocks
-                    .get(&(sand_pos.0 + 1))?
-                    .range(sand_pos.1 + 1..)
-                    .next()?
-                    != &(sand_pos.1 + 1)
-                {
-                    sand_pos.0 += 1;
-                    sand_pos.1 += 1;
-                } else {
+        if self.floor.is_some() {
+            self.cover(start.0);
+        }
+
+        let mut path = self.paths.remove(&start).unwrap_or_default();
+        if path.is_empty() {
+            path.push(start);
+        }
+        let rested = self.follow(&mut path);
+        self.paths.insert(start, path);
+
+        // Anything else that fell through there has to stop short of it now.
+        if let Some(pos) = rested {
+            for path in self.paths.values_mut() {
+                if let Some(i) = path.iter().position(|p| *p == pos) {
+                    path.truncate(i);
+                }
+            }
+        }
+
+        rested
+    }
+
+    /// Moves a grain on from the end of `path` until it rests or falls out.
+    fn follow(&mut self, path: &mut Vec<(usize, usize)>) -> Option<(usize, usize)> {
+        while let Some(&sand_pos) = path.last() {
+            if self.is_blocked(sand_pos) {
+                return None;
+            }
+
+            // down, then down-left, then down-right
+            let next = [
+                Some(sand_pos.0),
+                sand_pos.0.checked_sub(1),
+                Some(sand_pos.0 + 1),
+            ]
+            .into_iter()
+            .map(|column| column.map(|column| (column, sand_pos.1 + 1)))
+            .find(|pos| pos.is_none_or(|pos| !self.is_blocked(pos)));
+
+            match next {
+                // Started under the floor, so there's nothing to land on.
+                Some(Some(pos)) if self.floor.is_some_and(|floor| pos.1 > floor) => return None,
+                Some(Some(pos)) if self.cell(pos).is_some() => path.push(pos),
+                Some(Some(pos)) if self.floor.is_some() => {
+                    self.cover(pos.0);
+                    path.push(pos);
+                }
+                Some(_) => return None, // Into the void
+                None => {
                     // otherwise, rest here
+                    path.pop();
+                    let (x, y) = self.cell(sand_pos)?;
+                    self.sand.set(x, y);
                     return Some(sand_pos);
                 }
-            } else {
-                return None;
             }
         }
 
         None
     }
 
-    fn fill_with_sand(&mut self, start: (usize, usize)) -> Result<usize, Error> {
-        let mut sand = 0;
-        while let Some(pos) = self.add_sand(start) {
-            sand += 1;
+    /// Pours sand from every source in turn, a grain at a time, until one of
+    /// `until` happens. If none of them do, it keeps going until nothing more
+    /// can settle.
+    pub fn pour(&mut self, until: &[Stop]) -> Pour {
+        self.pour_with(until, usize::MAX, |_| Ok(())).unwrap()
+    }
 
-            if !self.sand.insert(pos) {
-                panic!("Added sand to filled location (sand)! pos: {:?}", pos);
+    /// Same as `pour`, but hands the cave to `frame` before the first grain,
+    /// every `every` grains, and once it's done.
+    fn pour_with(
+        &mut self,
+        until: &[Stop],
+        every: usize,
+        mut frame: impl FnMut(&Cave) -> io::Result<()>,
+    ) -> io::Result<Pour> {
+        // Size it up front so every frame is the same size.
+        if let Some(floor) = self.floor {
+            for source in self.sources.clone() {
+                self.cover(source.0.saturating_sub(floor));
+                self.cover(source.0 + floor);
             }
+        }
+
+        frame(self)?;
 
-            self.rocks.entry(pos.0).and_modify(|p| {
-                if !p.insert(pos.1) {
-                    panic!("Added sand to filled location (rock)! pos: {:?}", pos);
+        let limit = until
+            .iter()
+            .filter_map(|stop| match stop {
+                Stop::Grains(n) => Some(*n),
+                _ => None,
+            })
+            .min();
+        let mut grains = vec![0; self.sources.len()];
+        let mut total = 0;
+
+        let stop = 'pour: loop {
+            // Why nothing settled this time around, if it didn't.
+            let mut stuck = Stop::Blocked;
+            let mut settled = false;
+
+            for (i, source) in self.sources.clone().into_iter().enumerate() {
+                if limit.is_some_and(|limit| total >= limit) {
+                    break 'pour Stop::Grains(total);
+                }
+
+                if self.is_blocked(source) {
+                    if until.contains(&Stop::Blocked) {
+                        break 'pour Stop::Blocked;
+                    }
+                } else if self.add_sand(source).is_some() {
+                    grains[i] += 1;
+                    total += 1;
+                    settled = true;
+
+                    if total % every == 0 {
+                        frame(self)?;
+                    }
+                } else {
+                    if until.contains(&Stop::Void) {
+                        break 'pour Stop::Void;
+                    }
+                    stuck = Stop::Void;
                 }
-            });
+            }
+
+            if !settled {
+                break stuck;
+            }
+        };
+
+        if total % every != 0 {
+            frame(self)?;
         }
 
-        Ok(sand) // Fall off map; empty column
+        Ok(Pour { grains, stop })
     }
+
+    fn fill_with_sand(&mut self, start: (usize, usize)) -> Result<usize, Error> {
+        self.set_sources(&[start]);
+
+        Ok(self.pour(&[Stop::Void]).total()) // Fall off map; empty column
+    }
+
     fn fill_to_top(&mut self, start: (usize, usize)) -> Result<usize, Error> {
-        let mut sand = 0;
-        while let Some(pos) = self.add_sand(start) {
-            sand += 1;
+        self.set_sources(&[start]);
 
-            if pos == (500, 0) {
-                return Ok(sand);
-            }
+        let pour = self.pour(&[Stop::Blocked]);
+        if pour.stop == Stop::Void {
+            panic!("Fell off map!") // Fall off map; empty column
+        }
 
-            if !self.sand.insert(pos) {
-                panic!("Added sand to filled location (sand)! pos: {:?}", pos);
+        Ok(pour.total())
+    }
+
+    /// Fills the cave until a source is blocked or sand falls out, handing
+    /// the cave to `frame` along the way like `pour_with`.
+    fn animate(
+        &mut self,
+        every: usize,
+        frame: impl FnMut(&Cave) -> io::Result<()>,
+    ) -> io::Result<usize> {
+        if every == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "need at least one grain per frame",
+            ));
+        }
+
+        self.pour_with(&[Stop::Blocked, Stop::Void], every, frame)
+            .map(|pour| pour.total())
+    }
+
+    /// The cave as a binary PPM image, one pixel per cell.
+    fn write_ppm(&self, out: &mut impl Write) -> io::Result<()> {
+        write!(out, "P6\n{} {}\n255\n", self.width, self.rows())?;
+
+        for row in 0..self.rows() {
+            for column in self.left..self.left + self.width {
+                let pixel = if self.sources.contains(&(column, row)) {
+                    [255, 0, 0]
+                } else if self.is_sand((column, row)) {
+                    [230, 190, 90]
+                } else if self.is_rock((column, row)) {
+                    [100, 100, 100]
+                } else {
+                    [20, 20, 30]
+                };
+                out.write_all(&pixel)?;
             }
+        }
 
-            self.rocks.entry(pos.0).and_modify(|p| {
-                if !p.insert(pos.1) {
-                    panic!("Added sand to filled location (rock)! pos: {:?}", pos);
-                }
-            });
+        Ok(())
+    }
+
+    /// Counts the sand that `fill_to_top` would settle, without dropping any.
+    ///
+    /// With a floor, the pile ends up as the whole triangle under `start`,
+    /// minus the rocks and anything shadowed by them. A cell gets sand if it's
+    /// open and one of the three cells above it has sand, so it can be worked
+    /// out a row at a time.
+    fn count_to_top(&self, start: (usize, usize)) -> usize {
+        let floor = self.floor.expect("No floor to pile up on!");
+        if start.1 >= floor || self.is_rock(start) {
+            return 0;
         }
 
-        panic!("Fell off map!") // Fall off map; empty column
+        // Column `start.0 + i - offset` is at `row[i]`, wide enough for the
+        // pile to spread one column each way per row.
+        let offset = floor - start.1 + 1;
+        let mut row = vec![false; 2 * offset + 1];
+        row[offset] = true;
+        let mut sand = 1;
+
+        for y in start.1 + 1..floor {
+            let next: Vec<bool> = (0..row.len())
+                .map(|i| {
+                    let above = (i.saturating_sub(1)..=(i + 1).min(row.len() - 1)).any(|i| row[i]);
+                    let rock = (start.0 + i)
+                        .checked_sub(offset)
+                        .is_some_and(|column| self.is_rock((column, y)));
+
+                    above && !rock
+                })
+                .collect();
+
+            sand += next.iter().filter(|&&cell| cell).count();
+            row = next;
+        }
+
+        sand
     }
 }
 
@@ -229,12 +498,62 @@ pub fn part_1(input: &str) -> usize {
 
 pub fn part_2(input: &str) -> usize {
     let mut cave = input.parse::<Cave>().unwrap();
-    cave.add_floor(2, 300, 800);
+    cave.add_floor(2);
     cave.fill_to_top((500, 0)).unwrap()
-    // println!("{}", cave);
-    // let mut file = File::create("view_data.txt").unwrap();
-    // Write::write_all(&mut file, format!("{}", cave).as_bytes()).unwrap();
-    // sand
+}
+
+/// Part 2 without the simulation.
+pub fn part_2_counted(input: &str) -> usize {
+    let mut cave = input.parse::<Cave>().unwrap();
+    cave.add_floor(2);
+    cave.count_to_top((500, 0))
+}
+
+fn animation_cave(input: &str, floor: bool) -> Cave {
+    let mut cave = input.parse::<Cave>().unwrap();
+    if floor {
+        cave.add_floor(2);
+    }
+    cave
+}
+
+/// Plays the cave filling up in a terminal, drawing a frame every `every`
+/// grains over the top of the last one. Returns the grains dropped.
+pub fn animate_terminal(
+    input: &str,
+    floor: bool,
+    every: usize,
+    out: &mut impl Write,
+) -> io::Result<usize> {
+    let mut cave = animation_cave(input, floor);
+    let mut first = true;
+
+    cave.animate(every, |cave| {
+        if !first {
+            // Back up to the top left of the last frame.
+            write!(out, "\x1b[{}A\r", cave.rows())?;
+        }
+        first = false;
+
+        write!(out, "{}", cave)?;
+        out.flush()
+    })
+}
+
+/// Writes the cave filling up as numbered `.ppm` images in `dir`, one every
+/// `every` grains. Returns the grains dropped.
+pub fn animate_images(input: &str, floor: bool, every: usize, dir: &Path) -> io::Result<usize> {
+    let mut cave = animation_cave(input, floor);
+    let mut n = 0;
+
+    cave.animate(every, |cave| {
+        let file = File::create(dir.join(format!("frame_{:05}.ppm", n)))?;
+        n += 1;
+
+        let mut out = BufWriter::new(file);
+        cave.write_ppm(&mut out)?;
+        out.flush()
+    })
 }
 
 #[cfg(test)]
@@ -279,4 +598,122 @@ mod tests {
 
         println!("{:?}", part_2(&input))
     }
+
+    #[test]
+    fn test_part_2_counted() {
+        let input = fs::read_to_string(TEST_INPUT).unwrap();
+
+        assert_eq!(part_2_counted(&input), 93)
+    }
+
+    #[test]
+    fn check_part_2_counted() {
+        let input = fs::read_to_string(INPUT).unwrap();
+
+        assert_eq!(part_2_counted(&input), part_2(&input))
+    }
+
+    #[test]
+    fn check_animate_terminal() {
+        let input = fs::read_to_string(TEST_INPUT).unwrap();
+        let mut out = Vec::new();
+
+        assert_eq!(animate_terminal(&input, false, 5, &mut out).unwrap(), 24);
+
+        let out = String::from_utf8(out).unwrap();
+        let frames: Vec<&str> = out.split("\x1b[10A\r").collect();
+        assert_eq!(frames.len(), 6);
+        assert!(!frames[0].contains('o'));
+        assert_eq!(frames[5].matches('o').count(), 24);
+
+        let error = animate_terminal(&input, false, 0, &mut Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn check_animate_images() {
+        let input = fs::read_to_string(TEST_INPUT).unwrap();
+        let dir = std::env::temp_dir().join("d14_regolith_resrvoir_frames");
+        fs::create_dir_all(&dir).unwrap();
+
+        assert_eq!(animate_images(&input, true, 10, &dir).unwrap(), 93);
+
+        // 93 grains at 10 per frame, plus the empty cave and the last frame.
+        let last = fs::read(dir.join("frame_00010.ppm")).unwrap();
+        assert!(last.starts_with(b"P6\n"));
+        assert!(!dir.join("frame_00011.ppm").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn check_pour() {
+        let input = fs::read_to_string(TEST_INPUT).unwrap();
+
+        let mut cave = input.parse::<Cave>().unwrap();
+        assert_eq!(
+            cave.pour(&[Stop::Void]),
+            Pour {
+                grains: vec![24],
+                stop: Stop::Void
+            }
+        );
+
+        // Far enough apart that the piles don't touch.
+        let mut cave = input.parse::<Cave>().unwrap();
+        cave.add_floor(2);
+        cave.set_sources(&[(500, 0), (1000, 0)]);
+        assert_eq!(
+            cave.pour(&[Stop::Grains(10)]),
+            Pour {
+                grains: vec![5, 5],
+                stop: Stop::Grains(10)
+            }
+        );
+        assert_eq!(
+            cave.pour(&[]),
+            Pour {
+                grains: vec![93 - 5, 121 - 5],
+                stop: Stop::Blocked
+            }
+        );
+
+        // Close enough that they share a pile.
+        let mut cave = input.parse::<Cave>().unwrap();
+        cave.add_floor(2);
+        cave.set_sources(&[(500, 0), (498, 0)]);
+        let pour = cave.pour(&[]);
+        assert_eq!(pour.stop, Stop::Blocked);
+        assert_eq!(pour.total(), cave.to_string().matches(['o', '+']).count());
+    }
+
+    #[test]
+    fn check_source_under_floor() {
+        let input = fs::read_to_string(TEST_INPUT).unwrap();
+
+        let mut cave = input.parse::<Cave>().unwrap();
+        cave.add_floor(2);
+        cave.set_sources(&[(500, 50)]);
+        assert_eq!(
+            cave.pour(&[Stop::Grains(3)]),
+            Pour {
+                grains: vec![0],
+                stop: Stop::Void
+            }
+        );
+    }
+
+    #[test]
+    fn check_floor() {
+        // Spreads well past where a fixed floor used to end.
+        let mut cave = "0,300 -> 0,300".parse::<Cave>().unwrap();
+        cave.add_floor(2);
+        assert_eq!(cave.fill_to_top((500, 0)).unwrap(), 302 * 302);
+
+        // Nowhere near the rocks.
+        let input = fs::read_to_string(TEST_INPUT).unwrap();
+        let mut cave = input.parse::<Cave>().unwrap();
+        cave.add_floor(2);
+        assert_eq!(cave.fill_to_top((1000, 0)).unwrap(), 11 * 11);
+    }
 }
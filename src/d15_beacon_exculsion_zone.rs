@@ -1,15 +1,39 @@
 use std::{collections::BTreeSet, ops::Range, str::FromStr};
 
-type Position = (i64, i64);
+use crate::interval_set::IntervalSet;
+
+pub type Position = (i64, i64);
 
 #[derive(Debug)]
 pub struct ParseSensorError;
 
+/// How far apart two positions are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Metric {
+    /// Steps along the grid, so a sensor covers a diamond. What the puzzle uses.
+    #[default]
+    Manhattan,
+    /// Steps including diagonals, so a sensor covers a square.
+    Chebyshev,
+}
+
+impl Metric {
+    pub fn distance(&self, a: Position, b: Position) -> i64 {
+        let (dx, dy) = ((a.0 - b.0).abs(), (a.1 - b.1).abs());
+
+        match self {
+            Metric::Manhattan => dx + dy,
+            Metric::Chebyshev => dx.max(dy),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Sensor {
     position: Position,
     range: i64,
     beacons: Vec<Position>,
+    metric: Metric,
 }
 
 impl FromStr for Sensor {
@@ -46,111 +70,243 @@ impl FromStr for Sensor {
             .parse()
             .unwrap();
         let beacon = (x, y);
-        let beacons = vec![beacon];
-
-        let range = (position.0 - beacon.0).abs() + (position.1 - beacon.1).abs();
 
-        Ok(Sensor {
-            position,
-            range,
-            beacons,
-        })
+        Ok(Sensor::new(position, beacon))
     }
 }
 
 impl Sensor {
-    fn coverage_y(self, row: i64) -> Option<Range<i64>> {
-        let distance = (self.position.1 - row).abs();
-
-        match (self.range as i64).cmp(&distance) {
-            std::cmp::Ordering::Less => None,
-            std::cmp::Ordering::Equal => Some(Range {
-                start: self.position.0,
-                end: self.position.0 + 1,
-            }),
-            std::cmp::Ordering::Greater => Some(Range {
-                start: self.position.0 - (self.range - distance),
-                end: self.position.0 + (self.range - distance) + 1,
-            }),
+    /// A sensor at `position` whose closest beacon is at `beacon`.
+    pub fn new(position: Position, beacon: Position) -> Self {
+        Sensor {
+            position,
+            range: Metric::Manhattan.distance(position, beacon),
+            beacons: vec![beacon],
+            metric: Metric::Manhattan,
         }
     }
 
-    fn uncovered_y(self, row: i64, range: Range<i64>) -> Option<Vec<Range<i64>>> {
-        let Some(covered) = self.coverage_y(row) else { return Some(vec![range]) };
-
-        if covered.contains(&range.start) && covered.contains(&(range.end - 1)) {
-            None
-        } else if range.contains(&covered.start)
-            && range.contains(&(covered.end))
-            && range.start != covered.start
-        {
-            Some(vec![range.start..covered.start, covered.end..range.end])
-        } else if covered.contains(&range.start) {
-            Some(vec![covered.end..range.end])
-        } else if covered.contains(&(range.end - 1)) {
-            Some(vec![range.start..covered.start])
-        } else {
-            Some(vec![range])
+    /// Measures the range with `metric` instead.
+    pub fn with_metric(self, metric: Metric) -> Self {
+        Sensor {
+            range: metric.distance(self.position, self.beacons[0]),
+            metric,
+            ..self
         }
     }
-}
 
-fn no_coverage_y(sensors: &[Sensor], row_region: Range<i64>, row: i64) -> Option<(i64, i64)> {
-    let mut undetected = vec![row_region];
-    sensors.iter().for_each(|sensor| {
-        // dbg!(sensor.clone().coverage_y(row));
-        let mut new_segments = Vec::new();
-        for segment in &undetected {
-            if let Some(segments) = sensor.clone().uncovered_y(row, segment.clone()) {
-                new_segments.extend(segments);
-            }
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    /// Distance to the closest beacon. Anything within it is covered.
+    pub fn range(&self) -> i64 {
+        self.range
+    }
+
+    pub fn beacons(&self) -> &[Position] {
+        &self.beacons
+    }
+
+    pub fn metric(&self) -> Metric {
+        self.metric
+    }
+
+    pub fn covers(&self, position: Position) -> bool {
+        self.metric.distance(self.position, position) <= self.range
+    }
+
+    /// The x's this sensor covers on `row`, if any.
+    pub fn coverage_y(&self, row: i64) -> Option<Range<i64>> {
+        let distance = (self.position.1 - row).abs();
+        if distance > self.range {
+            return None;
         }
-        undetected = new_segments;
 
-        // dbg!(&undetected);
-    });
+        let reach = match self.metric {
+            Metric::Manhattan => self.range - distance,
+            Metric::Chebyshev => self.range,
+        };
 
-    // dbg!(row, &undetected);
-    Some((undetected.first()?.start, row))
+        Some(self.position.0 - reach..self.position.0 + reach + 1)
+    }
 }
 
-fn coverage_y(sensors: Vec<Sensor>, beacons: BTreeSet<Position>, row: i64) -> usize {
-    let coverage: BTreeSet<i64> = sensors
+/// Every x covered on `row`, merged from each sensor's slice of it.
+fn covered_y(sensors: &[Sensor], row: i64) -> IntervalSet {
+    sensors
         .iter()
-        .flat_map(|s| {
-            s.clone()
-                .coverage_y(row)
-                .unwrap_or(Range { start: 0, end: 0 })
-        })
-        .collect();
+        .filter_map(|sensor| sensor.coverage_y(row))
+        .collect()
+}
 
-    let row_beacons: BTreeSet<i64> = beacons
-        .iter()
-        .filter(|(_, y)| y == &row)
-        .map(|(x, _)| *x)
-        .collect();
+fn coverage_y(sensors: &[Sensor], beacons: &BTreeSet<Position>, row: i64) -> usize {
+    let mut coverage = covered_y(sensors, row);
+
+    for (x, _) in beacons.iter().filter(|(_, y)| y == &row) {
+        coverage.remove(*x..*x + 1);
+    }
 
-    coverage
-        .difference(&row_beacons)
-        .collect::<BTreeSet<&i64>>()
-        .len()
+    coverage.len() as usize
 }
 
 pub fn part_1(input: &str, row: i64) -> usize {
     let sensors = parse(input);
-    let beacons: BTreeSet<Position> = sensors.iter().flat_map(|s| s.beacons.clone()).collect();
-    coverage_y(sensors, beacons, row)
+    let beacons: BTreeSet<Position> = sensors.iter().flat_map(|s| s.beacons()).copied().collect();
+    coverage_y(&sensors, &beacons, row)
 }
 
 pub fn part_2(input: &str, min: i64, max: i64) -> Option<i64> {
+    part_2_tuned(input, min, max, tuning_frequency)
+}
+
+/// Part 2 with some other way of turning the position into a frequency.
+pub fn part_2_tuned(
+    input: &str,
+    min: i64,
+    max: i64,
+    tuning: impl Fn(Position) -> i64,
+) -> Option<i64> {
     let sensors = parse(input);
 
-    for row in min..=max {
-        if let Some((x, y)) = no_coverage_y(&sensors, min..max, row) {
-            return Some(x * 4_000_000 + y);
+    find_beacon(&sensors, min, max).map(tuning)
+}
+
+/// The only spot in the `min..=max` square that no sensor covers.
+///
+/// Being the only one, it's boxed in on every side: by the edges just
+/// outside the sensors' ranges, or by the edge of the square. So it's
+/// where two of those edges cross, and there aren't many of those to check.
+pub fn find_beacon(sensors: &[Sensor], min: i64, max: i64) -> Option<Position> {
+    // Lines of x + y, x - y, x and y one step outside each sensor.
+    let mut sums = Vec::new();
+    let mut differences = Vec::new();
+    let mut columns = vec![min, max];
+    let mut rows = vec![min, max];
+    for sensor in sensors {
+        let (x, y) = sensor.position;
+        let outside = sensor.range + 1;
+
+        match sensor.metric {
+            Metric::Manhattan => {
+                sums.extend([x + y - outside, x + y + outside]);
+                differences.extend([x - y - outside, x - y + outside]);
+            }
+            Metric::Chebyshev => {
+                columns.extend([x - outside, x + outside]);
+                rows.extend([y - outside, y + outside]);
+            }
+        }
+    }
+
+    let mut candidates = Vec::new();
+    for &sum in sums.iter() {
+        for &difference in differences.iter() {
+            if (sum + difference).rem_euclid(2) == 0 {
+                candidates.push(((sum + difference) / 2, (sum - difference) / 2));
+            }
+        }
+    }
+    for &x in columns.iter() {
+        candidates.extend(rows.iter().map(|&y| (x, y)));
+        candidates.extend(sums.iter().map(|&sum| (x, sum - x)));
+        candidates.extend(differences.iter().map(|&difference| (x, x - difference)));
+    }
+    for &y in rows.iter() {
+        candidates.extend(sums.iter().map(|&sum| (sum - y, y)));
+        candidates.extend(differences.iter().map(|&difference| (difference + y, y)));
+    }
+
+    candidates.into_iter().find(|&(x, y)| {
+        (min..=max).contains(&x)
+            && (min..=max).contains(&y)
+            && !sensors.iter().any(|sensor| sensor.covers((x, y)))
+    })
+}
+
+/// A rectangle of positions, with both corners included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub min: Position,
+    pub max: Position,
+}
+
+impl Region {
+    pub fn new(min: Position, max: Position) -> Self {
+        Region { min, max }
+    }
+
+    fn columns(&self) -> Range<i64> {
+        self.min.0..self.max.0 + 1
+    }
+
+    fn rows(&self) -> Range<i64> {
+        self.min.1..self.max.1 + 1
+    }
+}
+
+/// Draws the region like the puzzle does: `S` for sensors, `B` for beacons,
+/// `#` where a sensor can see and `.` where none can.
+pub fn render(sensors: &[Sensor], region: Region) -> String {
+    let beacons: BTreeSet<Position> = sensors.iter().flat_map(|s| s.beacons()).copied().collect();
+    let mut output = String::new();
+
+    for y in region.rows() {
+        let covered = covered_y(sensors, y);
+
+        for x in region.columns() {
+            if sensors.iter().any(|sensor| sensor.position == (x, y)) {
+                output.push('S');
+            } else if beacons.contains(&(x, y)) {
+                output.push('B');
+            } else if covered.contains(x) {
+                output.push('#');
+            } else {
+                output.push('.');
+            }
         }
+        output.push('\n');
     }
-    None
+
+    output
+}
+
+/// Number of positions in the region some sensor can see, including the
+/// sensors and beacons themselves.
+pub fn covered_in(sensors: &[Sensor], region: Region) -> u64 {
+    let width = (region.max.0 - region.min.0 + 1) as u64;
+
+    region
+        .rows()
+        .map(|y| width - covered_y(sensors, y).gaps(region.columns()).len())
+        .sum()
+}
+
+/// Every position in the region no sensor can see, row by row.
+pub fn uncovered_in(sensors: &[Sensor], region: Region) -> Vec<Position> {
+    region
+        .rows()
+        .flat_map(|y| {
+            covered_y(sensors, y)
+                .gaps(region.columns())
+                .ranges()
+                .iter()
+                .flat_map(|range| range.clone().map(move |x| (x, y)))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// The sensors that can see `position`.
+pub fn covering(sensors: &[Sensor], position: Position) -> Vec<&Sensor> {
+    sensors
+        .iter()
+        .filter(|sensor| sensor.covers(position))
+        .collect()
+}
+
+pub fn tuning_frequency(position: Position) -> i64 {
+    position.0 * 4_000_000 + position.1
 }
 
 pub fn parse(input: &str) -> Vec<Sensor> {
@@ -193,6 +349,88 @@ mod tests {
         assert_eq!(part_2(&input, 0, 20), Some(56000011))
     }
 
+    #[test]
+    fn check_find_beacon() {
+        let input = fs::read_to_string(TEST_INPUT).unwrap();
+        let sensors = parse(&input);
+
+        assert_eq!(find_beacon(&sensors, 0, 20), Some((14, 11)));
+
+        // Tucked into a corner, with only one diagonal next to it.
+        let sensors = parse("Sensor at x=0, y=0: closest beacon is at x=9, y=0");
+        assert_eq!(find_beacon(&sensors, 0, 5), Some((5, 5)));
+    }
+
+    #[test]
+    fn check_render() {
+        let input = fs::read_to_string(TEST_INPUT).unwrap();
+        let sensors = parse(&input);
+        let map = render(&sensors, Region::new((-4, 9), (26, 11)));
+
+        assert_eq!(
+            map.lines().collect::<Vec<_>>(),
+            vec![
+                "...#########################...",
+                "..####B######################..",
+                ".###S#############.###########.",
+            ]
+        );
+    }
+
+    #[test]
+    fn check_region() {
+        let input = fs::read_to_string(TEST_INPUT).unwrap();
+        let sensors = parse(&input);
+        let region = Region::new((0, 0), (20, 20));
+
+        assert_eq!(uncovered_in(&sensors, region), vec![(14, 11)]);
+        assert_eq!(covered_in(&sensors, region), 21 * 21 - 1);
+        assert_eq!(
+            covered_in(&sensors, Region::new((-10, 10), (30, 10))),
+            part_1(&input, 10) as u64 + 1
+        );
+
+        let seen_by: Vec<Position> = covering(&sensors, (14, 12))
+            .iter()
+            .map(|sensor| sensor.position)
+            .collect();
+        assert_eq!(seen_by, vec![(12, 14), (14, 17), (20, 14)]);
+        assert!(covering(&sensors, (14, 11)).is_empty());
+    }
+
+    #[test]
+    fn check_sensor() {
+        let sensor = Sensor::new((8, 7), (2, 10));
+        assert_eq!(sensor.range(), 9);
+        assert_eq!(sensor.coverage_y(10), Some(2..15));
+        assert_eq!(sensor.coverage_y(16), Some(8..9));
+        assert_eq!(sensor.coverage_y(17), None);
+
+        let sensor = sensor.with_metric(Metric::Chebyshev);
+        assert_eq!(sensor.range(), 6);
+        assert_eq!(sensor.coverage_y(10), Some(2..15));
+        assert_eq!(sensor.coverage_y(13), Some(2..15));
+        assert_eq!(sensor.coverage_y(14), None);
+
+        // Four squares around a one cell gap.
+        let sensors: Vec<Sensor> = [(0, 3), (3, 0), (6, 3), (3, 6)]
+            .into_iter()
+            .map(|position| Sensor::new(position, (position.0 + 2, position.1 + 2)))
+            .map(|sensor| sensor.with_metric(Metric::Chebyshev))
+            .collect();
+        assert_eq!(find_beacon(&sensors, 1, 5), Some((3, 3)));
+    }
+
+    #[test]
+    fn check_part_2_tuned() {
+        let input = fs::read_to_string(TEST_INPUT).unwrap();
+
+        assert_eq!(
+            part_2_tuned(&input, 0, 20, |(x, y)| x * 100 + y),
+            Some(1411)
+        )
+    }
+
     #[test]
     fn run_part_2() {
         let input = fs::read_to_string(INPUT).unwrap();
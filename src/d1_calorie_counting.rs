@@ -1,12 +1,15 @@
 // https://adventofcode.com/2022/day/1
 use std::{
-    cmp::max,
-    fs::File,
-    io::{BufRead, BufReader},
+    cmp::{max, Reverse},
+    collections::BinaryHeap,
+    fs,
+    num::ParseIntError,
 };
 
+const INPUT: &str = "src\\d1_calorie_counting_input.txt";
+
 pub fn result_part_1() {
-    let inv = read_inventory();
+    let inv = read_inventory(INPUT);
     let max_inv = count_calories(inv);
     print!("{}", max_inv);
 }
@@ -21,52 +24,104 @@ fn count_calories(inventory: Vec<Vec<usize>>) -> usize {
 }
 
 pub fn result_part_2() {
-    let inv = read_inventory();
+    let inv = read_inventory(INPUT);
     let max_inv: Vec<usize> = count_top_x(3, inv);
     print!("{:?}", max_inv.iter().sum::<usize>());
 }
 
 fn count_top_x(n: usize, inventory: Vec<Vec<usize>>) -> Vec<usize> {
-    let mut inv_vec = Vec::<usize>::new();
-    inv_vec.resize(n + 1, 0);
+    top_elves(n, &inventory)
+        .into_iter()
+        .map(|(_, calories)| calories)
+        .collect()
+}
 
-    for inv in inventory {
-        inv_vec[0] = inv.iter().sum();
-        inv_vec.sort();
+/// The `n` elves carrying the most, as (index, calories), most first. Ties go
+/// to whoever comes first.
+///
+/// Only ever holds `n` elves at once: the heap is a min-heap, so the weakest
+/// of the current top is always the one to get bumped.
+pub fn top_elves(n: usize, inventory: &[Vec<usize>]) -> Vec<(usize, usize)> {
+    let mut top = BinaryHeap::with_capacity(n + 1);
+
+    for (index, inv) in inventory.iter().enumerate() {
+        let calories: usize = inv.iter().sum();
+        top.push(Reverse((calories, Reverse(index))));
+        if top.len() > n {
+            top.pop();
+        }
     }
 
-    inv_vec.reverse();
-    inv_vec.resize(n, 0);
-    inv_vec
+    top.into_sorted_vec()
+        .into_iter()
+        .map(|Reverse((calories, Reverse(index)))| (index, calories))
+        .collect()
 }
 
-fn read_inventory() -> Vec<Vec<usize>> {
-    let filepath = "src\\d1_calorie_counting_input.txt";
-    let file = File::open(filepath).expect("Error opening file.");
-    let reader = BufReader::new(file);
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stats {
+    pub total: usize,
+    pub mean: f64,
+    pub median: f64,
+}
+
+/// Totals across all the elves. Nothing for nobody.
+pub fn stats(inventory: &[Vec<usize>]) -> Option<Stats> {
+    if inventory.is_empty() {
+        return None;
+    }
+
+    let mut totals: Vec<usize> = inventory.iter().map(|inv| inv.iter().sum()).collect();
+    totals.sort_unstable();
+
+    let total: usize = totals.iter().sum();
+    let middle = totals.len() / 2;
+    let median = if totals.len().is_multiple_of(2) {
+        (totals[middle - 1] + totals[middle]) as f64 / 2.0
+    } else {
+        totals[middle] as f64
+    };
+
+    Some(Stats {
+        total,
+        mean: total as f64 / totals.len() as f64,
+        median,
+    })
+}
 
+/// Each elf's snacks, with elves separated by blank lines.
+pub fn parse_inventory(input: &str) -> Result<Vec<Vec<usize>>, ParseIntError> {
     let mut inv_vec = Vec::<Vec<usize>>::new();
     let mut current_inv = Vec::<usize>::new();
-    for line in reader.lines() {
-        match line {
-            Ok(line) => match line.parse::<usize>() {
-                Ok(value) => current_inv.push(value),
-                Err(_) => {
-                    inv_vec.push(current_inv.clone());
-                    current_inv.clear();
-                }
-            },
-            Err(error) => panic!("{}", error),
+
+    for line in input.lines().map(str::trim) {
+        if line.is_empty() {
+            if !current_inv.is_empty() {
+                inv_vec.push(std::mem::take(&mut current_inv));
+            }
+        } else {
+            current_inv.push(line.parse()?);
         }
     }
+    if !current_inv.is_empty() {
+        inv_vec.push(current_inv);
+    }
+
+    Ok(inv_vec)
+}
 
-    inv_vec
+fn read_inventory(filepath: &str) -> Vec<Vec<usize>> {
+    let input = fs::read_to_string(filepath).expect("Error opening file.");
+
+    parse_inventory(&input).expect("Error reading inventory.")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const TEST_INPUT: &str = "1000\n2000\n3000\n\n4000\n\n5000\n6000\n\n7000\n8000\n9000\n\n10000";
+
     #[test]
     fn one_entry() {
         let inv = vec![vec![100, 200]];
@@ -82,7 +137,7 @@ mod tests {
     #[allow(clippy::print_with_newline)]
     #[test]
     fn read_bytes() {
-        for line in read_inventory() {
+        for line in read_inventory(INPUT) {
             print!("{:?}\n", line);
         }
     }
@@ -94,10 +149,10 @@ mod tests {
 
     #[test]
     fn check_p2_eq_p1() {
-        let inventory = read_inventory();
+        let inventory = read_inventory(INPUT);
         assert_eq!(
             count_calories(inventory.clone()),
-            count_top_x(1, inventory).iter().sum()
+            count_top_x(1, inventory).iter().sum::<usize>()
         );
     }
 
@@ -105,4 +160,50 @@ mod tests {
     fn get_result_2() {
         result_part_2();
     }
+
+    #[test]
+    fn check_parse() {
+        let inventory = parse_inventory(TEST_INPUT).unwrap();
+        assert_eq!(inventory.len(), 5);
+        assert_eq!(inventory[4], vec![10000]);
+
+        let inventory = parse_inventory("1\r\n2\r\n\r\n3\r\n").unwrap();
+        assert_eq!(inventory, vec![vec![1, 2], vec![3]]);
+
+        assert!(parse_inventory("1\nlots\n").is_err());
+    }
+
+    #[test]
+    fn check_top_elves() {
+        let inventory = parse_inventory(TEST_INPUT).unwrap();
+
+        assert_eq!(
+            top_elves(3, &inventory),
+            [(3, 24000), (2, 11000), (4, 10000)]
+        );
+        assert_eq!(
+            count_top_x(3, inventory.clone()).iter().sum::<usize>(),
+            45000
+        );
+        assert_eq!(top_elves(10, &inventory).len(), 5);
+        assert!(top_elves(0, &inventory).is_empty());
+
+        // Equal loads keep the earlier elf.
+        let inventory = vec![vec![5], vec![7], vec![5]];
+        assert_eq!(top_elves(2, &inventory), [(1, 7), (0, 5)]);
+    }
+
+    #[test]
+    fn check_stats() {
+        let inventory = parse_inventory(TEST_INPUT).unwrap();
+
+        let summary = stats(&inventory).unwrap();
+        assert_eq!(summary.total, 55000);
+        assert_eq!(summary.mean, 11000.0);
+        assert_eq!(summary.median, 10000.0);
+
+        let inventory = vec![vec![1], vec![4], vec![2], vec![10]];
+        assert_eq!(stats(&inventory).unwrap().median, 3.0);
+        assert_eq!(stats(&[]), None);
+    }
 }
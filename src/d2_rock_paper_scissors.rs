@@ -1,92 +1,260 @@
-use std::{
-    fs::File,
-    io::{BufRead, BufReader},
-};
+use std::{fmt, fs};
 
-pub fn sum_score() -> u32 {
-    let rounds = txt_input();
-    let mut score: u32 = 0;
-    for round in rounds {
-        score += round_score(round);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shape {
+    Rock,
+    Paper,
+    Scissors,
+}
+
+impl Shape {
+    pub const ALL: [Shape; 3] = [Shape::Rock, Shape::Paper, Shape::Scissors];
+
+    pub fn value(self) -> u32 {
+        match self {
+            Shape::Rock => 1,
+            Shape::Paper => 2,
+            Shape::Scissors => 3,
+        }
     }
 
-    score
-}
+    /// The shape this one beats.
+    pub fn beats(self) -> Shape {
+        match self {
+            Shape::Rock => Shape::Scissors,
+            Shape::Paper => Shape::Rock,
+            Shape::Scissors => Shape::Paper,
+        }
+    }
 
-pub fn sum_round_2() -> u32 {
-    let rounds = txt_input();
-    let mut score: u32 = 0;
-    for round in rounds {
-        score += round_2_score(round);
+    /// How playing this against `other` turns out.
+    pub fn against(self, other: Shape) -> Outcome {
+        if self.beats() == other {
+            Outcome::Win
+        } else if other.beats() == self {
+            Outcome::Lose
+        } else {
+            Outcome::Draw
+        }
     }
 
-    score
+    /// What to play against this to get `outcome`.
+    pub fn answer_for(self, outcome: Outcome) -> Shape {
+        Shape::ALL
+            .into_iter()
+            .find(|shape| shape.against(self) == outcome)
+            .unwrap()
+    }
 }
 
-#[allow(clippy::identity_op)]
-fn round_score(round: String) -> u32 {
-    match &*round {
-        "A X" => 3 + 1,
-        "A Y" => 6 + 2,
-        "A Z" => 0 + 3,
-        "B X" => 0 + 1,
-        "B Y" => 3 + 2,
-        "B Z" => 6 + 3,
-        "C X" => 6 + 1,
-        "C Y" => 0 + 2,
-        "C Z" => 3 + 3,
-        _ => 0,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Lose,
+    Draw,
+    Win,
+}
+
+impl Outcome {
+    pub fn value(self) -> u32 {
+        match self {
+            Outcome::Lose => 0,
+            Outcome::Draw => 3,
+            Outcome::Win => 6,
+        }
     }
 }
 
-#[allow(clippy::identity_op)]
-fn round_2_score(round: String) -> u32 {
-    match &*round {
-        "A X" => 0 + 3, // Lose v rock: scissors
-        "A Y" => 3 + 1, // tie v rock: rock
-        "A Z" => 6 + 2, // win v rock: paper
-        "B X" => 0 + 1, // lose v paper: rock
-        "B Y" => 3 + 2, // tie: paper
-        "B Z" => 6 + 3, // win v paper: scissors
-        "C X" => 0 + 2, // lose v scissors: paper
-        "C Y" => 3 + 3, // tie: scissors
-        "C Z" => 6 + 1, // win v scissors: rock
-        _ => 0,
+/// What the second column of the guide means.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// `X`, `Y` and `Z` are the shape to play. The first guess in part 1.
+    Shapes,
+    /// `X`, `Y` and `Z` are how the round has to end. What the elf meant.
+    Outcomes,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Round {
+    pub opponent: Shape,
+    pub response: Shape,
+}
+
+impl Round {
+    pub fn parse(line: &str, strategy: Strategy) -> Result<Round, ParseRoundErrorKind> {
+        let mut columns = line.split_whitespace();
+
+        let opponent = match columns.next() {
+            Some("A") => Shape::Rock,
+            Some("B") => Shape::Paper,
+            Some("C") => Shape::Scissors,
+            Some(other) => return Err(ParseRoundErrorKind::UnknownOpponent(other.to_string())),
+            None => return Err(ParseRoundErrorKind::MissingColumn),
+        };
+
+        let column = match columns.next() {
+            Some(column @ ("X" | "Y" | "Z")) => column,
+            Some(other) => return Err(ParseRoundErrorKind::UnknownResponse(other.to_string())),
+            None => return Err(ParseRoundErrorKind::MissingColumn),
+        };
+
+        if columns.next().is_some() {
+            return Err(ParseRoundErrorKind::TrailingInput);
+        }
+
+        let response = match strategy {
+            Strategy::Shapes => match column {
+                "X" => Shape::Rock,
+                "Y" => Shape::Paper,
+                _ => Shape::Scissors,
+            },
+            Strategy::Outcomes => opponent.answer_for(match column {
+                "X" => Outcome::Lose,
+                "Y" => Outcome::Draw,
+                _ => Outcome::Win,
+            }),
+        };
+
+        Ok(Round { opponent, response })
+    }
+
+    pub fn outcome(&self) -> Outcome {
+        self.response.against(self.opponent)
+    }
+
+    pub fn score(&self) -> u32 {
+        self.response.value() + self.outcome().value()
     }
 }
 
-fn txt_input() -> Vec<String> {
-    let fp = "src\\d2_rock_paper_scissors.txt";
-    let file = File::open(fp).expect("Error opening file.");
-    let reader = BufReader::new(file);
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseRoundError {
+    /// Counting from 1, like an editor.
+    pub line: usize,
+    pub kind: ParseRoundErrorKind,
+}
 
-    let mut vec_lines = Vec::<String>::new();
-    for line in reader.lines() {
-        vec_lines.push(line.expect("Error reading line"));
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseRoundErrorKind {
+    MissingColumn,
+    UnknownOpponent(String),
+    UnknownResponse(String),
+    TrailingInput,
+}
+
+impl fmt::Display for ParseRoundError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            ParseRoundErrorKind::MissingColumn => write!(f, "expected two columns"),
+            ParseRoundErrorKind::UnknownOpponent(s) => {
+                write!(f, "unknown opponent shape '{}'", s)
+            }
+            ParseRoundErrorKind::UnknownResponse(s) => write!(f, "unknown response '{}'", s),
+            ParseRoundErrorKind::TrailingInput => write!(f, "unexpected input after round"),
+        }
     }
+}
 
-    vec_lines
+impl std::error::Error for ParseRoundError {}
+
+/// Every round in the guide. Blank lines are skipped.
+pub fn parse_guide(input: &str, strategy: Strategy) -> Result<Vec<Round>, ParseRoundError> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            Round::parse(line, strategy).map_err(|kind| ParseRoundError { line: i + 1, kind })
+        })
+        .collect()
+}
+
+pub fn total_score(input: &str, strategy: Strategy) -> Result<u32, ParseRoundError> {
+    Ok(parse_guide(input, strategy)?.iter().map(Round::score).sum())
+}
+
+pub fn sum_score() -> u32 {
+    total_score(&txt_input(), Strategy::Shapes).expect("Error reading guide.")
+}
+
+pub fn sum_round_2() -> u32 {
+    total_score(&txt_input(), Strategy::Outcomes).expect("Error reading guide.")
+}
+
+fn txt_input() -> String {
+    let fp = "src\\d2_rock_paper_scissors.txt";
+    fs::read_to_string(fp).expect("Error opening file.")
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::d2_rock_paper_scissors::sum_round_2;
+    use super::*;
 
-    use super::{round_score, sum_score, txt_input};
+    const TEST_INPUT: &str = "A Y\nB X\nC Z\n";
 
     #[test]
     fn check_input() {
         let inp = txt_input();
-        for line in inp {
+        for line in inp.lines() {
             println!("{}", line);
         }
     }
 
     #[test]
     fn test_scoring() {
-        assert_eq!(round_score("C X".to_string()), 6 + 1);
-        assert_eq!(round_score("C Y".to_string()), 2);
-        assert_eq!(round_score("C Z".to_string()), 3 + 3);
+        let score = |line| Round::parse(line, Strategy::Shapes).unwrap().score();
+
+        assert_eq!(score("C X"), 6 + 1);
+        assert_eq!(score("C Y"), 2);
+        assert_eq!(score("C Z"), 3 + 3);
+    }
+
+    #[test]
+    fn check_rules() {
+        for shape in Shape::ALL {
+            assert_eq!(shape.against(shape), Outcome::Draw);
+            assert_eq!(shape.against(shape.beats()), Outcome::Win);
+            assert_eq!(shape.beats().against(shape), Outcome::Lose);
+            for outcome in [Outcome::Lose, Outcome::Draw, Outcome::Win] {
+                assert_eq!(shape.answer_for(outcome).against(shape), outcome);
+            }
+        }
+    }
+
+    #[test]
+    fn check_strategies() {
+        assert_eq!(total_score(TEST_INPUT, Strategy::Shapes), Ok(15));
+        assert_eq!(total_score(TEST_INPUT, Strategy::Outcomes), Ok(12));
+
+        let round = Round::parse("A X", Strategy::Outcomes).unwrap();
+        assert_eq!(round.response, Shape::Scissors);
+        assert_eq!(round.outcome(), Outcome::Lose);
+    }
+
+    #[test]
+    fn check_errors() {
+        let error = |line, kind| Err(ParseRoundError { line, kind });
+
+        assert_eq!(
+            parse_guide("A Y\nD X", Strategy::Shapes),
+            error(2, ParseRoundErrorKind::UnknownOpponent("D".to_string()))
+        );
+        assert_eq!(
+            parse_guide("A W", Strategy::Outcomes),
+            error(1, ParseRoundErrorKind::UnknownResponse("W".to_string()))
+        );
+        assert_eq!(
+            parse_guide("A Y\n\nB", Strategy::Shapes),
+            error(3, ParseRoundErrorKind::MissingColumn)
+        );
+        assert_eq!(
+            parse_guide("A Y Z", Strategy::Shapes),
+            error(1, ParseRoundErrorKind::TrailingInput)
+        );
+        assert_eq!(
+            parse_guide("AY", Strategy::Shapes).unwrap_err().to_string(),
+            "line 1: unknown opponent shape 'AY'"
+        );
     }
 
     #[test]
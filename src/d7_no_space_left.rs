@@ -1,106 +1,573 @@
-use std::collections::BTreeSet;
+use std::{collections::BTreeMap, fmt, str::FromStr};
 
-pub fn sum_small_files(data: &str, max_size: u32) -> u32 {
-    // I need a stack. When the value gets popped off the stack, add it to the top of the stack. if that value is small, add it to my total
-    let mut total = 0;
-    let mut dirstack = Vec::<u32>::new();
-
-    for line in data.lines() {
-        let mut line_parts = line.split_whitespace();
-
-        match line_parts.next() {
-            Some("$") => match line_parts.next() {
-                Some("cd") => match line_parts.next() {
-                    Some("..") => {
-                        let v = dirstack.pop().unwrap();
-                        if v <= max_size {
-                            total += v;
-                        }
-                        if !dirstack.is_empty() {
-                            let i = dirstack.len() - 1;
-                            dirstack[i] += v;
-                        }
+/// Index of a node in a [`FileSystem`].
+pub type NodeId = usize;
+
+/// What's wrong with a transcript, and on which line (counting from 1).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseFileSystemError {
+    pub line: usize,
+    pub kind: ParseFileSystemErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseFileSystemErrorKind {
+    /// `$` with nothing after it, or `$ cd` with nowhere to go.
+    MissingCommand,
+    UnknownCommand(String),
+    /// A `dir` or file line without a name.
+    MissingName,
+    BadSize(String),
+    /// `ls` output with no `ls` before it.
+    UnexpectedOutput,
+    /// `cd ..` from `/`.
+    AboveRoot,
+    /// `cd` into something that hasn't been listed.
+    UndeclaredDirectory(String),
+    /// `cd` into a file.
+    NotADirectory(String),
+    /// Listed again, but as something else.
+    Conflict(String),
+}
+
+impl fmt::Display for ParseFileSystemError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+
+        match &self.kind {
+            ParseFileSystemErrorKind::MissingCommand => write!(f, "missing command"),
+            ParseFileSystemErrorKind::UnknownCommand(command) => {
+                write!(f, "unknown command '{}'", command)
+            }
+            ParseFileSystemErrorKind::MissingName => write!(f, "missing name"),
+            ParseFileSystemErrorKind::BadSize(size) => write!(f, "bad size '{}'", size),
+            ParseFileSystemErrorKind::UnexpectedOutput => write!(f, "output without 'ls'"),
+            ParseFileSystemErrorKind::AboveRoot => write!(f, "'cd ..' from '/'"),
+            ParseFileSystemErrorKind::UndeclaredDirectory(name) => {
+                write!(f, "'cd' into unlisted directory '{}'", name)
+            }
+            ParseFileSystemErrorKind::NotADirectory(name) => {
+                write!(f, "'cd' into file '{}'", name)
+            }
+            ParseFileSystemErrorKind::Conflict(name) => {
+                write!(f, "'{}' listed again as something else", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseFileSystemError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NodeKind {
+    Dir(BTreeMap<String, NodeId>),
+    File(u32),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    pub name: String,
+    pub parent: Option<NodeId>,
+    pub kind: NodeKind,
+}
+
+/// Order to list the contents of a directory in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortBy {
+    /// The order `ls` gave them.
+    #[default]
+    Listed,
+    Name,
+    /// Biggest first.
+    Size,
+}
+
+/// How [`FileSystem::render`] draws the tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TreeOptions {
+    /// Show the total size of directories too, not just files.
+    pub sizes: bool,
+    pub sort: SortBy,
+    /// Deepest level to show, with the root at 0.
+    pub max_depth: Option<usize>,
+}
+
+/// The directory tree pieced together from a terminal transcript.
+///
+/// Nodes live in one `Vec`, with the root at 0. A node is always added after
+/// its parent, so its id is always bigger.
+#[derive(Debug, Clone)]
+pub struct FileSystem {
+    nodes: Vec<Node>,
+}
+
+impl FileSystem {
+    pub const ROOT: NodeId = 0;
+
+    pub fn new() -> Self {
+        FileSystem {
+            nodes: vec![Node {
+                name: "/".to_string(),
+                parent: None,
+                kind: NodeKind::Dir(BTreeMap::new()),
+            }],
+        }
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id]
+    }
+
+    pub fn is_dir(&self, id: NodeId) -> bool {
+        matches!(self.nodes[id].kind, NodeKind::Dir(_))
+    }
+
+    /// Child of `dir` called `name`, if there is one.
+    pub fn child(&self, dir: NodeId, name: &str) -> Option<NodeId> {
+        match &self.nodes[dir].kind {
+            NodeKind::Dir(children) => children.get(name).copied(),
+            NodeKind::File(_) => None,
+        }
+    }
+
+    /// Adds a node under `dir`, or finds the one already there.
+    fn add(&mut self, dir: NodeId, name: &str, kind: NodeKind) -> NodeId {
+        if let Some(id) = self.child(dir, name) {
+            return id;
+        }
+
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+            name: name.to_string(),
+            parent: Some(dir),
+            kind,
+        });
+        if let NodeKind::Dir(children) = &mut self.nodes[dir].kind {
+            children.insert(name.to_string(), id);
+        }
+
+        id
+    }
+
+    /// Finds a node from an absolute path like `/a/e`.
+    pub fn lookup(&self, path: &str) -> Option<NodeId> {
+        path.split('/')
+            .filter(|name| !name.is_empty())
+            .try_fold(FileSystem::ROOT, |dir, name| self.child(dir, name))
+    }
+
+    /// Absolute path of a node.
+    pub fn path(&self, id: NodeId) -> String {
+        let mut names = Vec::new();
+        let mut node = Some(id);
+        while let Some(id) = node.filter(|&id| id != FileSystem::ROOT) {
+            names.push(self.nodes[id].name.as_str());
+            node = self.nodes[id].parent;
+        }
+        names.reverse();
+
+        format!("/{}", names.join("/"))
+    }
+
+    /// Total size of every node, including everything under it, by id.
+    pub fn sizes(&self) -> Vec<u32> {
+        let mut sizes: Vec<u32> = self
+            .nodes
+            .iter()
+            .map(|node| match node.kind {
+                NodeKind::File(size) => size,
+                NodeKind::Dir(_) => 0,
+            })
+            .collect();
+
+        // Children come after their parents, so going backwards every node
+        // is finished before it's added to its parent.
+        for id in (1..self.nodes.len()).rev() {
+            if let Some(parent) = self.nodes[id].parent {
+                sizes[parent] += sizes[id];
+            }
+        }
+
+        sizes
+    }
+
+    pub fn size(&self, id: NodeId) -> u32 {
+        self.sizes()[id]
+    }
+
+    /// Draws the tree the way the puzzle does:
+    ///
+    /// ```text
+    /// - / (dir)
+    ///   - a (dir)
+    ///     - i (file, size=584)
+    /// ```
+    pub fn render(&self, options: &TreeOptions) -> String {
+        let sizes = self.sizes();
+        let mut output = String::new();
+        self.render_node(FileSystem::ROOT, 0, options, &sizes, &mut output);
+
+        output
+    }
+
+    fn render_node(
+        &self,
+        id: NodeId,
+        depth: usize,
+        options: &TreeOptions,
+        sizes: &[u32],
+        output: &mut String,
+    ) {
+        let node = &self.nodes[id];
+        let indent = "  ".repeat(depth);
+
+        match &node.kind {
+            NodeKind::File(size) => {
+                output.push_str(&format!(
+                    "{}- {} (file, size={})\n",
+                    indent, node.name, size
+                ));
+            }
+            NodeKind::Dir(children) => {
+                if options.sizes {
+                    output.push_str(&format!(
+                        "{}- {} (dir, size={})\n",
+                        indent, node.name, sizes[id]
+                    ));
+                } else {
+                    output.push_str(&format!("{}- {} (dir)\n", indent, node.name));
+                }
+
+                if options.max_depth.is_some_and(|max| depth >= max) {
+                    return;
+                }
+
+                // Ids go up in the order things were listed.
+                let mut children: Vec<NodeId> = children.values().copied().collect();
+                match options.sort {
+                    SortBy::Listed => children.sort(),
+                    SortBy::Name => {
+                        children.sort_by(|&a, &b| self.nodes[a].name.cmp(&self.nodes[b].name))
                     }
-                    Some(_) => dirstack.push(0),
-                    None => panic!("'cd' missing dir!"),
-                },
-                Some(_) => {} // don't care about ls
-                None => panic!("'$' missing command!"),
-            },
-            Some("dir") => {} // don't care about dir in ls
-            Some(i) => {
-                let size = i.parse::<u32>().expect("Failed to parse into number!");
-                let i = dirstack.len() - 1;
-                dirstack[i] += size;
+                    SortBy::Size => children.sort_by(|&a, &b| sizes[b].cmp(&sizes[a])),
+                }
+
+                for child in children {
+                    self.render_node(child, depth + 1, options, sizes, output);
+                }
+            }
+        }
+    }
+
+    /// Every directory with its total size.
+    pub fn directories(&self) -> Vec<(NodeId, u32)> {
+        let sizes = self.sizes();
+
+        (0..self.nodes.len())
+            .filter(|&id| self.is_dir(id))
+            .map(|id| (id, sizes[id]))
+            .collect()
+    }
+}
+
+/// Most memory `plan_set` will use on its search, in bits. 128MB.
+pub const MAX_PLAN_BITS: usize = 1 << 30;
+
+/// Directories to delete to make room, and where that leaves the disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CleanupPlan {
+    pub delete: Vec<NodeId>,
+    pub freed: u32,
+    /// Free space once they're gone.
+    pub free_after: u32,
+}
+
+impl FileSystem {
+    /// Space that still has to be freed to have `needed_space` free.
+    fn shortfall(&self, total_space: u32, needed_space: u32) -> u32 {
+        let free = total_space.saturating_sub(self.size(FileSystem::ROOT));
+        needed_space.saturating_sub(free)
+    }
+
+    fn plan(&self, total_space: u32, delete: Vec<NodeId>) -> CleanupPlan {
+        let sizes = self.sizes();
+        let freed = delete.iter().map(|&id| sizes[id]).sum();
+
+        CleanupPlan {
+            delete,
+            freed,
+            // Can't go below nothing free, even if it's overfull to start with.
+            free_after: (total_space as u64 + freed as u64)
+                .saturating_sub(sizes[FileSystem::ROOT] as u64) as u32,
+        }
+    }
+
+    /// The smallest single directory that frees up enough space.
+    pub fn plan_single(&self, total_space: u32, needed_space: u32) -> Option<CleanupPlan> {
+        let shortfall = self.shortfall(total_space, needed_space);
+        if shortfall == 0 {
+            return Some(self.plan(total_space, Vec::new()));
+        }
+
+        let (id, _) = self
+            .directories()
+            .into_iter()
+            .filter(|&(_, size)| size >= shortfall)
+            .min_by_key(|&(_, size)| size)?;
+
+        Some(self.plan(total_space, vec![id]))
+    }
+
+    /// The directories, none inside another, that free up enough space while
+    /// deleting as little as possible.
+    ///
+    /// Costs a bit per directory per byte the best single directory would
+    /// free, about 100MB for a real puzzle input. Past `MAX_PLAN_BITS` it
+    /// doesn't try, and just hands back the single directory plan.
+    pub fn plan_set(&self, total_space: u32, needed_space: u32) -> Option<CleanupPlan> {
+        let shortfall = self.shortfall(total_space, needed_space);
+        if shortfall == 0 {
+            return Some(self.plan(total_space, Vec::new()));
+        }
+
+        // Nothing at or past the best single directory can win, so that
+        // bounds the sums worth tracking.
+        let single = self.plan_single(total_space, needed_space)?;
+        let limit = single.freed as usize;
+
+        // Directories in pre-order, each with the position just past its
+        // subdirectories. Taking one skips straight over everything inside it.
+        let sizes = self.sizes();
+        let dirs = self.preorder();
+        if (dirs.len() + 1).saturating_mul(limit) > MAX_PLAN_BITS {
+            return Some(single);
+        }
+
+        // reach[i] holds every sum under `limit` that positions from i onwards
+        // can free.
+        let mut reach = vec![Bitset::new(limit); dirs.len() + 1];
+        reach[dirs.len()].insert(0);
+        for (i, &(id, end)) in dirs.iter().enumerate().rev() {
+            let mut sums = reach[i + 1].clone();
+            sums.union_shifted(&reach[end], sizes[id] as usize);
+            reach[i] = sums;
+        }
+
+        let Some(mut left) = (shortfall as usize..limit).find(|&sum| reach[0].contains(sum)) else {
+            return Some(single);
+        };
+
+        let mut delete = Vec::new();
+        let mut i = 0;
+        while left > 0 {
+            let (id, end) = dirs[i];
+            if reach[i + 1].contains(left) {
+                i += 1;
+            } else {
+                delete.push(id);
+                left -= sizes[id] as usize;
+                i = end;
+            }
+        }
+
+        Some(self.plan(total_space, delete))
+    }
+
+    /// Directories depth first, each with the index just past its subtree.
+    fn preorder(&self) -> Vec<(NodeId, usize)> {
+        let mut dirs = Vec::new();
+        self.visit(FileSystem::ROOT, &mut dirs);
+        dirs
+    }
+
+    fn visit(&self, dir: NodeId, dirs: &mut Vec<(NodeId, usize)>) {
+        let index = dirs.len();
+        dirs.push((dir, 0));
+
+        if let NodeKind::Dir(children) = &self.nodes[dir].kind {
+            for &child in children.values().filter(|&&id| self.is_dir(id)) {
+                self.visit(child, dirs);
             }
-            None => panic!("Missing line!"),
         }
+
+        dirs[index].1 = dirs.len();
     }
 
-    while let Some(dir) = dirstack.pop() {
-        if dir <= max_size {
-            total += dir;
+    /// Report of a plan, one directory per line, then the free space left.
+    pub fn describe(&self, plan: &CleanupPlan) -> String {
+        let sizes = self.sizes();
+        let mut output = String::new();
+
+        for &id in plan.delete.iter() {
+            output.push_str(&format!("delete {} ({})\n", self.path(id), sizes[id]));
         }
+        output.push_str(&format!(
+            "freed {}, leaving {} free\n",
+            plan.freed, plan.free_after
+        ));
+
+        output
     }
-    total
 }
 
-pub fn find_smallest_useful_dir(data: &str, total_space: u32, needed_space: u32) -> u32 {
-    let sizes = list_directory_sizes(data);
-    let total_used: u32 = *sizes.iter().last().unwrap();
+/// Fixed size set of small numbers, for the cleanup knapsack.
+#[derive(Debug, Clone)]
+struct Bitset {
+    len: usize,
+    words: Vec<u64>,
+}
 
-    for size in sizes {
-        if total_used - size <= total_space - needed_space {
-            return size;
+impl Bitset {
+    fn new(len: usize) -> Self {
+        Bitset {
+            len,
+            words: vec![0; len.div_ceil(64)],
         }
     }
-    panic!()
+
+    fn insert(&mut self, n: usize) {
+        if n < self.len {
+            self.words[n / 64] |= 1 << (n % 64);
+        }
+    }
+
+    fn contains(&self, n: usize) -> bool {
+        n < self.len && self.words[n / 64] & (1 << (n % 64)) != 0
+    }
+
+    /// Adds `n + shift` for every `n` in `other`, dropping anything too big.
+    fn union_shifted(&mut self, other: &Bitset, shift: usize) {
+        let (whole, part) = (shift / 64, shift % 64);
+
+        for i in (whole..self.words.len()).rev() {
+            let from = i - whole;
+            let mut word = other.words[from] << part;
+            if part > 0 && from > 0 {
+                word |= other.words[from - 1] >> (64 - part);
+            }
+            self.words[i] |= word;
+        }
+
+        // Clear anything shifted past the end.
+        if !self.len.is_multiple_of(64) {
+            if let Some(last) = self.words.last_mut() {
+                *last &= (1 << (self.len % 64)) - 1;
+            }
+        }
+    }
+}
+
+impl fmt::Display for FileSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.render(&TreeOptions::default()))
+    }
 }
 
-fn list_directory_sizes(data: &str) -> BTreeSet<u32> {
-    let mut dirtree = BTreeSet::<u32>::new();
-    let mut dirstack = Vec::<u32>::new();
+impl Default for FileSystem {
+    fn default() -> Self {
+        FileSystem::new()
+    }
+}
+
+impl FromStr for FileSystem {
+    type Err = ParseFileSystemError;
+
+    // `cd /` goes back to the root from anywhere. Listing a directory again
+    // is fine as long as it lists the same things; they're only added once.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fs = FileSystem::new();
+        let mut cwd = FileSystem::ROOT;
+        let mut listing = false;
 
-    for line in data.lines() {
-        let mut line_parts = line.split_whitespace();
+        for (i, line) in s.lines().enumerate() {
+            let error = |kind| ParseFileSystemError { line: i + 1, kind };
+            let mut line_parts = line.split_whitespace();
 
-        match line_parts.next() {
-            Some("$") => match line_parts.next() {
-                Some("cd") => match line_parts.next() {
-                    Some("..") => {
-                        let v = dirstack.pop().unwrap();
+            match line_parts.next() {
+                Some("$") => {
+                    listing = false;
 
-                        if !dirstack.is_empty() {
-                            let i = dirstack.len() - 1;
-                            dirstack[i] += v;
+                    match line_parts.next() {
+                        Some("cd") => match line_parts.next() {
+                            Some("/") => cwd = FileSystem::ROOT,
+                            Some("..") => {
+                                cwd = fs.nodes[cwd]
+                                    .parent
+                                    .ok_or(error(ParseFileSystemErrorKind::AboveRoot))?
+                            }
+                            Some(name) => match fs.child(cwd, name) {
+                                Some(id) if fs.is_dir(id) => cwd = id,
+                                Some(_) => {
+                                    return Err(error(ParseFileSystemErrorKind::NotADirectory(
+                                        name.to_string(),
+                                    )))
+                                }
+                                None => {
+                                    return Err(error(
+                                        ParseFileSystemErrorKind::UndeclaredDirectory(
+                                            name.to_string(),
+                                        ),
+                                    ))
+                                }
+                            },
+                            None => return Err(error(ParseFileSystemErrorKind::MissingCommand)),
+                        },
+                        Some("ls") => listing = true,
+                        Some(command) => {
+                            return Err(error(ParseFileSystemErrorKind::UnknownCommand(
+                                command.to_string(),
+                            )))
                         }
-                        dirtree.insert(v);
+                        None => return Err(error(ParseFileSystemErrorKind::MissingCommand)),
                     }
-                    Some(_) => dirstack.push(0),
-                    None => panic!("'cd' missing dir!"),
-                },
-                Some(_) => {} // don't care about ls
-                None => panic!("'$' missing command!"),
-            },
-            Some("dir") => {} // don't care about dir in ls
-            Some(i) => {
-                let size = i.parse::<u32>().expect("Failed to parse into number!");
-                let i = dirstack.len() - 1;
-                dirstack[i] += size;
+                }
+                Some(_) if !listing => {
+                    return Err(error(ParseFileSystemErrorKind::UnexpectedOutput))
+                }
+                Some(entry) => {
+                    let kind = match entry {
+                        "dir" => NodeKind::Dir(BTreeMap::new()),
+                        size => NodeKind::File(size.parse().map_err(|_| {
+                            error(ParseFileSystemErrorKind::BadSize(size.to_string()))
+                        })?),
+                    };
+                    let name = line_parts
+                        .next()
+                        .ok_or(error(ParseFileSystemErrorKind::MissingName))?;
+
+                    let id = fs.add(cwd, name, kind.clone());
+                    let same = match (&fs.nodes[id].kind, &kind) {
+                        (NodeKind::Dir(_), NodeKind::Dir(_)) => true,
+                        (a, b) => a == b,
+                    };
+                    if !same {
+                        return Err(error(ParseFileSystemErrorKind::Conflict(name.to_string())));
+                    }
+                }
+                None => {} // Blank line
             }
-            None => panic!("Missing line!"),
         }
-    }
 
-    while let Some(dir) = dirstack.pop() {
-        if !dirstack.is_empty() {
-            let i = dirstack.len() - 1;
-            dirstack[i] += dir;
-        }
-        dirtree.insert(dir);
+        Ok(fs)
     }
+}
+
+pub fn sum_small_files(data: &str, max_size: u32) -> u32 {
+    let fs: FileSystem = data.parse().unwrap();
+
+    fs.directories()
+        .into_iter()
+        .map(|(_, size)| size)
+        .filter(|&size| size <= max_size)
+        .sum()
+}
+
+pub fn find_smallest_useful_dir(data: &str, total_space: u32, needed_space: u32) -> u32 {
+    let fs: FileSystem = data.parse().unwrap();
 
-    dirtree
+    fs.plan_single(total_space, needed_space)
+        .expect("Not enough space, even deleting everything!")
+        .freed
 }
 
 #[cfg(test)]
@@ -160,4 +627,225 @@ mod tests {
             find_smallest_useful_dir(&input, 70_000_000, 30_000_000)
         );
     }
+
+    #[test]
+    fn check_file_system() {
+        let mut input = String::new();
+        File::open(TEST_INPUT)
+            .expect("Failed to open!")
+            .read_to_string(&mut input)
+            .expect("Failed to read file!");
+
+        let fs: FileSystem = input.parse().unwrap();
+        let e = fs.lookup("/a/e").unwrap();
+
+        assert_eq!(fs.path(e), "/a/e");
+        assert_eq!(fs.size(e), 584);
+        assert_eq!(fs.size(fs.lookup("/a").unwrap()), 94853);
+        assert_eq!(fs.size(fs.lookup("/d").unwrap()), 24933642);
+        assert_eq!(fs.size(FileSystem::ROOT), 48381165);
+        assert_eq!(
+            fs.node(fs.lookup("/d/d.log").unwrap()).kind,
+            NodeKind::File(8033020)
+        );
+        assert_eq!(fs.lookup("/a/x"), None);
+        assert_eq!(fs.directories().len(), 4);
+    }
+
+    #[test]
+    fn check_render() {
+        let mut input = String::new();
+        File::open(TEST_INPUT)
+            .expect("Failed to open!")
+            .read_to_string(&mut input)
+            .expect("Failed to read file!");
+
+        let fs: FileSystem = input.parse().unwrap();
+
+        assert_eq!(
+            fs.to_string(),
+            "\
+- / (dir)
+  - a (dir)
+    - e (dir)
+      - i (file, size=584)
+    - f (file, size=29116)
+    - g (file, size=2557)
+    - h.lst (file, size=62596)
+  - b.txt (file, size=14848514)
+  - c.dat (file, size=8504156)
+  - d (dir)
+    - j (file, size=4060174)
+    - d.log (file, size=8033020)
+    - d.ext (file, size=5626152)
+    - k (file, size=7214296)
+"
+        );
+
+        let options = TreeOptions {
+            sizes: true,
+            sort: SortBy::Size,
+            max_depth: Some(1),
+        };
+        assert_eq!(
+            fs.render(&options),
+            "\
+- / (dir, size=48381165)
+  - d (dir, size=24933642)
+  - b.txt (file, size=14848514)
+  - c.dat (file, size=8504156)
+  - a (dir, size=94853)
+"
+        );
+
+        let options = TreeOptions {
+            sort: SortBy::Name,
+            ..Default::default()
+        };
+        assert!(fs.render(&options).ends_with(
+            "\
+    - d.ext (file, size=5626152)
+    - d.log (file, size=8033020)
+    - j (file, size=4060174)
+    - k (file, size=7214296)
+"
+        ));
+    }
+
+    #[test]
+    fn check_transcript_handling() {
+        let transcript = "\
+$ cd /
+$ ls
+dir a
+10 b
+$ cd a
+$ ls
+20 c
+$ cd /
+$ ls
+dir a
+10 b
+$ cd a
+$ ls
+20 c";
+        let fs: FileSystem = transcript.parse().unwrap();
+
+        assert_eq!(fs.size(FileSystem::ROOT), 30);
+        assert_eq!(fs.size(fs.lookup("/a").unwrap()), 20);
+        assert_eq!(fs.directories().len(), 2);
+
+        let error = |line, kind| Err(ParseFileSystemError { line, kind });
+        let parse = |s: &str| s.parse::<FileSystem>().map(|_| ());
+
+        assert_eq!(
+            parse("$ cd /\n$ cd a"),
+            error(
+                2,
+                ParseFileSystemErrorKind::UndeclaredDirectory("a".to_string())
+            )
+        );
+        assert_eq!(
+            parse("$ ls\n10 b\n$ cd b"),
+            error(3, ParseFileSystemErrorKind::NotADirectory("b".to_string()))
+        );
+        assert_eq!(
+            parse("$ cd .."),
+            error(1, ParseFileSystemErrorKind::AboveRoot)
+        );
+        assert_eq!(
+            parse("$ cd"),
+            error(1, ParseFileSystemErrorKind::MissingCommand)
+        );
+        assert_eq!(
+            parse("$ rm -rf"),
+            error(
+                1,
+                ParseFileSystemErrorKind::UnknownCommand("rm".to_string())
+            )
+        );
+        assert_eq!(
+            parse("$ ls\nten b"),
+            error(2, ParseFileSystemErrorKind::BadSize("ten".to_string()))
+        );
+        assert_eq!(
+            parse("$ ls\ndir"),
+            error(2, ParseFileSystemErrorKind::MissingName)
+        );
+        assert_eq!(
+            parse("10 b"),
+            error(1, ParseFileSystemErrorKind::UnexpectedOutput)
+        );
+        assert_eq!(
+            parse("$ ls\n10 b\n$ ls\n11 b"),
+            error(4, ParseFileSystemErrorKind::Conflict("b".to_string()))
+        );
+    }
+
+    #[test]
+    fn check_plans() {
+        let mut input = String::new();
+        File::open(TEST_INPUT)
+            .expect("Failed to open!")
+            .read_to_string(&mut input)
+            .expect("Failed to read file!");
+
+        let fs: FileSystem = input.parse().unwrap();
+
+        let plan = fs.plan_single(70_000_000, 30_000_000).unwrap();
+        assert_eq!(
+            fs.describe(&plan),
+            "delete /d (24933642)\nfreed 24933642, leaving 46552477 free\n"
+        );
+
+        // 8381165 short: /a and /a/e together would be nested, /d is too big.
+        let plan = fs.plan_set(70_000_000, 30_000_000).unwrap();
+        assert_eq!(plan.delete, vec![fs.lookup("/d").unwrap()]);
+
+        let plan = fs.plan_set(48_381_165 + 10_000, 100_000).unwrap();
+        assert_eq!(plan.delete, vec![fs.lookup("/a").unwrap()]);
+        assert_eq!(plan.free_after, 104853);
+
+        assert!(fs.plan_set(48_381_165, 0).unwrap().delete.is_empty());
+
+        // Far too big to search, so it settles for the one directory.
+        let mut transcript = "$ cd /\n$ ls\n100000000 big\n".to_string();
+        for i in 0..100 {
+            transcript.push_str(&format!("dir d{}\n", i));
+        }
+        let big: FileSystem = transcript.parse().unwrap();
+        let plan = big.plan_set(100_000_000, 50_000_000).unwrap();
+        assert_eq!(plan.delete, vec![FileSystem::ROOT]);
+        assert_eq!(fs.plan_set(48_381_165, 48_381_166), None);
+        assert_eq!(fs.plan_single(48_381_165, 48_381_166), None);
+
+        // Already using more than there is.
+        let plan = fs.plan_single(50, 0).unwrap();
+        assert_eq!(plan.free_after, 0);
+    }
+
+    #[test]
+    fn check_plan_set() {
+        let mut input = String::new();
+        File::open(INPUT)
+            .expect("Failed to open!")
+            .read_to_string(&mut input)
+            .expect("Failed to read file!");
+
+        let fs: FileSystem = input.parse().unwrap();
+        let single = fs.plan_single(70_000_000, 30_000_000).unwrap();
+        let set = fs.plan_set(70_000_000, 30_000_000).unwrap();
+
+        assert!(set.freed <= single.freed);
+        assert_eq!(set.freed, 3837783);
+        assert!(set.free_after >= 30_000_000);
+        for &a in set.delete.iter() {
+            for &b in set.delete.iter() {
+                assert!(a == b || !fs.path(b).starts_with(&format!("{}/", fs.path(a))));
+            }
+        }
+        assert!(fs
+            .describe(&set)
+            .ends_with("freed 3837783, leaving 30000000 free\n"));
+    }
 }
@@ -1,72 +1,267 @@
-use std::collections::BTreeSet;
+/// The edge a line of sight starts from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    North,
+    South,
+    East,
+    West,
+}
 
-pub fn count_visible_trees(input: &str) -> u32 {
-    let trees = parse_trees(input);
-    let mut visible_trees: BTreeSet<(usize, usize)> = BTreeSet::new();
+impl Direction {
+    pub const ALL: [Direction; 4] = [
+        Direction::North,
+        Direction::South,
+        Direction::East,
+        Direction::West,
+    ];
+
+    /// Every line of cells starting from this edge, in the order they're
+    /// passed going inwards, as indices into a `width` wide grid.
+    fn lines(self, width: usize, height: usize) -> Vec<Vec<usize>> {
+        match self {
+            Direction::North => (0..width)
+                .map(|x| (0..height).map(|y| y * width + x).collect())
+                .collect(),
+            Direction::South => (0..width)
+                .map(|x| (0..height).rev().map(|y| y * width + x).collect())
+                .collect(),
+            Direction::West => (0..height)
+                .map(|y| (0..width).map(|x| y * width + x).collect())
+                .collect(),
+            Direction::East => (0..height)
+                .map(|y| (0..width).rev().map(|x| y * width + x).collect())
+                .collect(),
+        }
+    }
+}
+
+/// Which edges a tree can be seen from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VisibleFrom([bool; 4]);
+
+impl VisibleFrom {
+    pub fn contains(&self, direction: Direction) -> bool {
+        self.0[direction as usize]
+    }
+
+    pub fn any(&self) -> bool {
+        self.0.iter().any(|&seen| seen)
+    }
+
+    pub fn directions(&self) -> Vec<Direction> {
+        Direction::ALL
+            .into_iter()
+            .filter(|&d| self.contains(d))
+            .collect()
+    }
+}
+
+/// How far a tree can see in each direction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct View([u32; 4]);
+
+impl View {
+    pub fn distance(&self, direction: Direction) -> u32 {
+        self.0[direction as usize]
+    }
+
+    pub fn score(&self) -> u32 {
+        self.0.iter().product()
+    }
+}
+
+/// Where the treehouse should go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Spot {
+    pub x: usize,
+    pub y: usize,
+    pub view: View,
+}
+
+/// Tree heights, row by row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Forest {
+    pub width: usize,
+    pub height: usize,
+    trees: Vec<u32>,
+}
 
-    //horizontal
-    for (y, row) in trees.iter().enumerate() {
-        let sighted = row.iter().enumerate().filter(|(i, v)| {
-            row[..*i].iter().all(|e| e < v) || row[*i + 1..].iter().all(|e| e < v)
-        });
+impl Forest {
+    pub fn parse(input: &str) -> Self {
+        let rows = parse_trees(input);
 
-        for (index, _value) in sighted {
-            visible_trees.insert((index, y));
+        Forest {
+            width: rows.first().map_or(0, |row| row.len()),
+            height: rows.len(),
+            trees: rows.concat(),
         }
     }
 
-    //vertical
-    for x in 0..trees[0].len() {
-        let column: Vec<u32> = trees.iter().map(|v| v[x]).collect();
+    pub fn tree(&self, x: usize, y: usize) -> u32 {
+        self.trees[y * self.width + x]
+    }
 
-        let sighted = column.iter().enumerate().filter(|(i, v)| {
-            column[..*i].iter().all(|e| e < v) || column[*i + 1..].iter().all(|e| e < v)
-        });
+    /// Whether each tree can be seen from the `from` edge. Only the tallest
+    /// so far along a line can be.
+    pub fn visible_from(&self, from: Direction) -> Vec<bool> {
+        let mut visible = vec![false; self.trees.len()];
 
-        for (index, _value) in sighted {
-            visible_trees.insert((x, index));
+        for line in from.lines(self.width, self.height) {
+            let mut tallest = None;
+            for i in line {
+                if tallest.is_none_or(|tallest| self.trees[i] > tallest) {
+                    visible[i] = true;
+                    tallest = Some(self.trees[i]);
+                }
+            }
         }
+
+        visible
     }
 
-    visible_trees.len() as u32
-}
+    /// How many trees each tree can see looking back towards the `towards`
+    /// edge.
+    ///
+    /// Keeps a stack of the trees passed so far that nothing since has been
+    /// as tall as. Anything shorter than the current tree can't block the
+    /// view for trees further in, so it gets popped, and whatever is left on
+    /// top is the one blocking the view.
+    pub fn viewing_distances(&self, towards: Direction) -> Vec<u32> {
+        let mut distances = vec![0; self.trees.len()];
 
-pub fn find_max_scenic_score(input: &str) -> u32 {
-    let trees = parse_trees(input);
-    let mut max_score: u32 = 0;
-
-    let width: usize = trees[0].len();
-    for (i, height) in trees.iter().flatten().enumerate() {
-        let x = i % width;
-        let y = i / width;
-
-        let row = trees[y].clone();
-        let column: Vec<u32> = trees.iter().map(|v| v[x]).collect();
-
-        let (north, south) = column.split_at(y);
-        let mut north = north.to_vec();
-        north.reverse();
-        let south = south.to_vec()[1..].to_vec();
-        let (east, west) = row.split_at(x);
-        let mut east = east.to_vec();
-        east.reverse();
-        let west = west.to_vec()[1..].to_vec();
-
-        let mut score: usize = 1;
-        for direction in [&north, &south, &east, &west] {
-            let subscore = direction
-                .split_inclusive(|v| v >= height)
-                .next()
-                .unwrap_or_default()
-                .len();
-
-            score *= subscore;
+        for line in towards.lines(self.width, self.height) {
+            let mut blockers: Vec<(usize, u32)> = Vec::new();
+            for (step, &i) in line.iter().enumerate() {
+                while blockers.last().is_some_and(|&(_, h)| h < self.trees[i]) {
+                    blockers.pop();
+                }
+
+                let blocked_at = blockers.last().map_or(0, |&(at, _)| at);
+                distances[i] = (step - blocked_at) as u32;
+                blockers.push((step, self.trees[i]));
+            }
+        }
+
+        distances
+    }
+
+    /// Whether each tree is visible from any edge.
+    pub fn visibility(&self) -> Vec<bool> {
+        self.visible_froms().iter().map(VisibleFrom::any).collect()
+    }
+
+    /// Scenic score of each tree, the product of its viewing distances.
+    pub fn scenic_scores(&self) -> Vec<u32> {
+        self.views().iter().map(View::score).collect()
+    }
+
+    fn visible_froms(&self) -> Vec<VisibleFrom> {
+        let mut visible = vec![VisibleFrom::default(); self.trees.len()];
+
+        for direction in Direction::ALL {
+            for (cell, seen) in visible.iter_mut().zip(self.visible_from(direction)) {
+                cell.0[direction as usize] = seen;
+            }
+        }
+
+        visible
+    }
+
+    fn views(&self) -> Vec<View> {
+        let mut views = vec![View::default(); self.trees.len()];
+
+        for direction in Direction::ALL {
+            for (view, distance) in views.iter_mut().zip(self.viewing_distances(direction)) {
+                view.0[direction as usize] = distance;
+            }
+        }
+
+        views
+    }
+
+    /// The edges each tree can be seen from, row by row.
+    pub fn visibility_map(&self) -> Vec<Vec<VisibleFrom>> {
+        self.rows(self.visible_froms())
+    }
+
+    /// Each tree's view in every direction, row by row.
+    pub fn view_map(&self) -> Vec<Vec<View>> {
+        self.rows(self.views())
+    }
+
+    fn rows<T: Clone>(&self, cells: Vec<T>) -> Vec<Vec<T>> {
+        if self.width == 0 {
+            return Vec::new();
         }
+        cells.chunks(self.width).map(|row| row.to_vec()).collect()
+    }
+
+    /// The tree with the best scenic score. Ties go to the first one reading
+    /// across then down.
+    pub fn best_spot(&self) -> Option<Spot> {
+        self.views()
+            .into_iter()
+            .enumerate()
+            .rev()
+            .max_by_key(|(_, view)| view.score())
+            .map(|(i, view)| Spot {
+                x: i % self.width,
+                y: i / self.width,
+                view,
+            })
+    }
+
+    /// The forest with hidden trees blanked out.
+    pub fn render_visibility(&self) -> String {
+        let visible = self.visibility();
+
+        self.render(|i| {
+            if visible[i] {
+                char::from_digit(self.trees[i], 10).unwrap_or('?')
+            } else {
+                '.'
+            }
+        })
+    }
+
+    /// Scenic scores as a heatmap, scaled against the best one, with the
+    /// best spot marked `X`.
+    pub fn render_scenic(&self) -> String {
+        const SHADES: &[u8] = b" .:-=+*#%@";
+
+        let scores = self.scenic_scores();
+        let best = self.best_spot();
+        let max = scores.iter().copied().max().unwrap_or(0).max(1) as u64;
 
-        max_score = max_score.max(score as u32);
+        self.render(|i| {
+            if best.is_some_and(|spot| spot.y * self.width + spot.x == i) {
+                return 'X';
+            }
+            let shade = scores[i] as u64 * (SHADES.len() as u64 - 1) / max;
+            SHADES[shade as usize] as char
+        })
     }
 
-    max_score
+    fn render(&self, cell: impl Fn(usize) -> char) -> String {
+        let mut output = String::new();
+        for y in 0..self.height {
+            output.extend((0..self.width).map(|x| cell(y * self.width + x)));
+            output.push('\n');
+        }
+        output
+    }
+}
+
+pub fn count_visible_trees(input: &str) -> u32 {
+    let forest = Forest::parse(input);
+
+    forest.visibility().into_iter().filter(|&seen| seen).count() as u32
+}
+
+pub fn find_max_scenic_score(input: &str) -> u32 {
+    let forest = Forest::parse(input);
+
+    forest.best_spot().map_or(0, |spot| spot.view.score())
 }
 
 fn parse_trees(input: &str) -> Vec<Vec<u32>> {
@@ -119,4 +314,94 @@ mod tests {
 
         println!("{:?}", find_max_scenic_score(&input))
     }
+
+    #[test]
+    fn check_visibility() {
+        let input = fs::read_to_string("src\\d8_treetop_tree_house.test").unwrap();
+        let forest = Forest::parse(&input);
+
+        let visible: Vec<bool> = forest.visibility();
+        let rows: Vec<String> = visible
+            .chunks(forest.width)
+            .map(|row| row.iter().map(|&v| if v { '#' } else { '.' }).collect())
+            .collect();
+        assert_eq!(rows, ["#####", "###.#", "##.##", "#.#.#", "#####"]);
+
+        let from_west = forest.visible_from(Direction::West);
+        assert!(from_west[forest.width + 1]);
+        assert!(!from_west[forest.width + 2]);
+    }
+
+    #[test]
+    fn check_scenic_scores() {
+        let input = fs::read_to_string("src\\d8_treetop_tree_house.test").unwrap();
+        let forest = Forest::parse(&input);
+        let at = |x: usize, y: usize| y * forest.width + x;
+
+        let north = forest.viewing_distances(Direction::North);
+        let south = forest.viewing_distances(Direction::South);
+        let west = forest.viewing_distances(Direction::West);
+        let east = forest.viewing_distances(Direction::East);
+        assert_eq!(
+            [
+                north[at(2, 3)],
+                west[at(2, 3)],
+                south[at(2, 3)],
+                east[at(2, 3)]
+            ],
+            [2, 2, 1, 2]
+        );
+
+        let scores = forest.scenic_scores();
+        assert_eq!(scores[at(2, 1)], 4);
+        assert_eq!(scores[at(2, 3)], 8);
+        assert_eq!(scores[at(0, 0)], 0);
+    }
+
+    #[test]
+    fn check_maps() {
+        let input = fs::read_to_string("src\\d8_treetop_tree_house.test").unwrap();
+        let forest = Forest::parse(&input);
+
+        let visibility = forest.visibility_map();
+        assert_eq!(
+            visibility[1][1].directions(),
+            [Direction::North, Direction::West]
+        );
+        assert_eq!(
+            visibility[1][2].directions(),
+            [Direction::North, Direction::East]
+        );
+        assert!(!visibility[1][3].any());
+        assert!(visibility[0][0].contains(Direction::North));
+        assert!(!visibility[0][0].contains(Direction::South));
+
+        let views = forest.view_map();
+        assert_eq!(views[3][2].distance(Direction::North), 2);
+        assert_eq!(views[3][2].distance(Direction::East), 2);
+        assert_eq!(views[3][2].score(), 8);
+
+        let best = forest.best_spot().unwrap();
+        assert_eq!((best.x, best.y, best.view.score()), (2, 3, 8));
+
+        let empty = Forest::parse("");
+        assert!(empty.visibility_map().is_empty());
+        assert!(empty.view_map().is_empty());
+        assert_eq!(empty.best_spot(), None);
+    }
+
+    #[test]
+    fn check_rendering() {
+        let input = fs::read_to_string("src\\d8_treetop_tree_house.test").unwrap();
+        let forest = Forest::parse(&input);
+
+        assert_eq!(
+            forest.render_visibility(),
+            "30373\n255.2\n65.32\n3.5.9\n35390\n"
+        );
+        assert_eq!(
+            forest.render_scenic(),
+            "     \n .=. \n *.: \n .X- \n     \n"
+        );
+    }
 }
@@ -1,150 +1,307 @@
-use std::collections::BTreeSet;
+use std::{collections::BTreeSet, fmt};
 
-#[derive(Debug, Clone, Copy)]
-enum Direction {
+pub type Position = (i32, i32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
     Up,
     Down,
     Left,
     Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+}
+
+impl Direction {
+    const CODES: [(&'static str, Direction); 8] = [
+        ("U", Direction::Up),
+        ("D", Direction::Down),
+        ("L", Direction::Left),
+        ("R", Direction::Right),
+        ("UL", Direction::UpLeft),
+        ("UR", Direction::UpRight),
+        ("DL", Direction::DownLeft),
+        ("DR", Direction::DownRight),
+    ];
+
+    fn step(self) -> Position {
+        match self {
+            Direction::Up => (0, 1),
+            Direction::Down => (0, -1),
+            Direction::Left => (-1, 0),
+            Direction::Right => (1, 0),
+            Direction::UpLeft => (-1, 1),
+            Direction::UpRight => (1, 1),
+            Direction::DownLeft => (-1, -1),
+            Direction::DownRight => (1, -1),
+        }
+    }
+
+    fn from_code(code: &str) -> Option<Direction> {
+        Direction::CODES
+            .iter()
+            .find(|&&(c, _)| c == code)
+            .map(|&(_, direction)| direction)
+    }
+
+    fn code(self) -> &'static str {
+        Direction::CODES
+            .iter()
+            .find(|&&(_, d)| d == self)
+            .map(|&(c, _)| c)
+            .unwrap()
+    }
 }
 
-struct Rope {
-    segments: Vec<RopeSegment>,
+/// How a knot catches up with the one ahead of it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FollowRule {
+    /// Moves once it's no longer touching, diagonally if it has to. What the
+    /// puzzle uses.
+    #[default]
+    Diagonal,
+    /// Only ever moves along the grid, and counts diagonal as too far.
+    Orthogonal,
+    /// Like `Diagonal`, but lets the gap grow to this much first.
+    Slack(u32),
+    /// Lets the gap grow to this much, then snaps right back to touching.
+    Elastic(u32),
 }
 
-impl Rope {
-    fn new(length: usize) -> Self {
-        let mut segments = Vec::new();
-        segments.resize(length, RopeSegment::new());
-        Rope { segments }
+impl FollowRule {
+    /// Whether a knot this far behind has to start moving.
+    fn pulled(self, (dx, dy): Position) -> bool {
+        match self {
+            FollowRule::Diagonal => dx.abs().max(dy.abs()) > 1,
+            FollowRule::Orthogonal => dx.abs() + dy.abs() > 1,
+            FollowRule::Slack(slack) | FollowRule::Elastic(slack) => {
+                dx.unsigned_abs().max(dy.unsigned_abs()) > slack
+            }
+        }
     }
 
-    fn get_tail_history(&self) -> BTreeSet<(i32, i32)> {
-        self.segments.last().unwrap().tail_history.clone()
+    /// Whether a knot that's moving can stop here.
+    fn settled(self, (dx, dy): Position) -> bool {
+        match self {
+            FollowRule::Elastic(_) => dx.abs().max(dy.abs()) <= 1,
+            _ => !self.pulled((dx, dy)),
+        }
     }
 
-    fn move_head(&mut self, direction: Direction) {
-        let head = self.segments.first().unwrap();
-        let mut x = match direction {
-            Direction::Up => head.head.0,
-            Direction::Down => head.head.0,
-            Direction::Left => head.head.0 - 1,
-            Direction::Right => head.head.0 + 1,
-        };
+    fn step(self, (dx, dy): Position) -> Position {
+        match self {
+            FollowRule::Orthogonal if dx.abs() >= dy.abs() => (dx.signum(), 0),
+            FollowRule::Orthogonal => (0, dy.signum()),
+            _ => (dx.signum(), dy.signum()),
+        }
+    }
+}
 
-        let mut y = match direction {
-            Direction::Up => head.head.1 + 1,
-            Direction::Down => head.head.1 - 1,
-            Direction::Left => head.head.1,
-            Direction::Right => head.head.1,
-        };
+/// The head moving some number of steps in one direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Motion {
+    pub direction: Direction,
+    pub steps: usize,
+}
 
-        self.segments.iter_mut().for_each(|segmnent| {
-            segmnent.put_head(x, y);
-            x = segmnent.tail.0;
-            y = segmnent.tail.1;
-        });
+impl fmt::Display for Motion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.direction.code(), self.steps)
     }
 }
 
+/// A rope of knots, the first being the head and the last the tail, each
+/// remembering everywhere it's been.
 #[derive(Debug, Clone)]
-struct RopeSegment {
-    head: (i32, i32),
-    tail: (i32, i32),
-    tail_history: BTreeSet<(i32, i32)>,
+pub struct Rope {
+    knots: Vec<Position>,
+    visited: Vec<BTreeSet<Position>>,
+    rule: FollowRule,
 }
 
-impl RopeSegment {
-    fn new() -> Self {
-        let mut tail_history = BTreeSet::new();
-        tail_history.insert((0, 0));
+impl Rope {
+    pub fn new(knots: usize) -> Self {
+        assert!(knots > 0, "A rope needs at least a head!");
 
-        RopeSegment {
-            head: (0, 0),
-            tail: (0, 0),
-            tail_history,
+        Rope {
+            knots: vec![(0, 0); knots],
+            visited: vec![BTreeSet::from([(0, 0)]); knots],
+            rule: FollowRule::default(),
         }
     }
 
-    fn move_head(&mut self, direction: Direction) {
-        match direction {
-            Direction::Up => self.head.1 += 1,
-            Direction::Down => self.head.1 -= 1,
-            Direction::Left => self.head.0 -= 1,
-            Direction::Right => self.head.0 += 1,
-        }
+    pub fn with_rule(self, rule: FollowRule) -> Self {
+        Rope { rule, ..self }
+    }
 
-        self.move_tail();
+    pub fn rule(&self) -> FollowRule {
+        self.rule
     }
 
-    fn put_head(&mut self, x: i32, y: i32) {
-        self.head = (x, y);
-        self.move_tail();
+    pub fn len(&self) -> usize {
+        self.knots.len()
     }
 
-    fn move_tail(&mut self) {
-        let mut dx = self.head.0 - self.tail.0;
-        let mut dy = self.head.1 - self.tail.1;
+    pub fn is_empty(&self) -> bool {
+        self.knots.is_empty()
+    }
 
-        while dx.abs() > 1 {
-            self.tail.0 += dx.signum();
-            self.tail.1 += dy.signum();
+    pub fn knots(&self) -> &[Position] {
+        &self.knots
+    }
 
-            dx = self.head.0 - self.tail.0;
-            dy = self.head.1 - self.tail.1;
-            self.tail_history.insert(self.tail);
+    pub fn knot(&self, knot: usize) -> Position {
+        self.knots[knot]
+    }
+
+    pub fn head(&self) -> Position {
+        self.knots[0]
+    }
+
+    pub fn tail(&self) -> Position {
+        self.knots[self.knots.len() - 1]
+    }
+
+    /// Everywhere `knot` has been, including where it started.
+    pub fn visited(&self, knot: usize) -> &BTreeSet<Position> {
+        &self.visited[knot]
+    }
+
+    pub fn tail_visited(&self) -> &BTreeSet<Position> {
+        &self.visited[self.knots.len() - 1]
+    }
+
+    pub fn apply(&mut self, motion: Motion) {
+        for _ in 0..motion.steps {
+            self.step(motion.direction);
+        }
+    }
+
+    /// Moves the head once, then lets every other knot catch up in turn.
+    fn step(&mut self, direction: Direction) {
+        let (dx, dy) = direction.step();
+        self.knots[0].0 += dx;
+        self.knots[0].1 += dy;
+        self.visited[0].insert(self.knots[0]);
+
+        for knot in 1..self.knots.len() {
+            self.follow(knot);
         }
+    }
 
-        while dy.abs() > 1 {
-            self.tail.0 += dx.signum();
-            self.tail.1 += dy.signum();
+    /// Moves a knot towards the one ahead of it for as long as the rule says.
+    fn follow(&mut self, knot: usize) {
+        let ahead = self.knots[knot - 1];
+        let gap = |(x, y): Position| (ahead.0 - x, ahead.1 - y);
 
-            dx = self.head.0 - self.tail.0;
-            dy = self.head.1 - self.tail.1;
-            self.tail_history.insert(self.tail);
+        if !self.rule.pulled(gap(self.knots[knot])) {
+            return;
+        }
+
+        while !self.rule.settled(gap(self.knots[knot])) {
+            let (x, y) = self.knots[knot];
+            let (dx, dy) = self.rule.step(gap((x, y)));
+
+            self.knots[knot] = (x + dx, y + dy);
+            self.visited[knot].insert(self.knots[knot]);
         }
     }
 }
 
-pub fn part_1(input: &str) -> usize {
-    let commands = parse(input);
-    let mut rope = RopeSegment::new();
+impl Rope {
+    /// How a knot gets drawn: `H` for the head, numbers for the ones after,
+    /// and `T` for the tail of a two knot rope or any tail past 9.
+    fn label(&self, knot: usize) -> char {
+        match knot {
+            0 => 'H',
+            1 if self.len() == 2 => 'T',
+            1..=9 => char::from_digit(knot as u32, 10).unwrap(),
+            _ if knot == self.len() - 1 => 'T',
+            _ => '*',
+        }
+    }
 
-    for command in commands {
-        rope.move_head(command);
+    /// Draws the rope like the puzzle does, with up at the top and `s` where
+    /// it started. With `visited`, it draws the tail's trail instead, `#`
+    /// everywhere it's been, and leaves the knots out. The window is just big
+    /// enough to fit it all.
+    pub fn render(&self, visited: bool) -> String {
+        let mut points: Vec<Position> = vec![(0, 0)];
+        if visited {
+            points.extend(self.tail_visited());
+        } else {
+            points.extend(self.knots.iter());
+        }
+
+        let min_x = points.iter().map(|p| p.0).min().unwrap();
+        let max_x = points.iter().map(|p| p.0).max().unwrap();
+        let min_y = points.iter().map(|p| p.1).min().unwrap();
+        let max_y = points.iter().map(|p| p.1).max().unwrap();
+
+        let mut output = String::new();
+        for y in (min_y..=max_y).rev() {
+            for x in min_x..=max_x {
+                // Earlier knots cover later ones, as in the puzzle.
+                let knot = self.knots.iter().position(|&k| k == (x, y));
+                let cell = match knot {
+                    Some(knot) if !visited => self.label(knot),
+                    _ if (x, y) == (0, 0) => 's',
+                    _ if visited && self.tail_visited().contains(&(x, y)) => '#',
+                    _ => '.',
+                };
+                output.push(cell);
+            }
+            output.push('\n');
+        }
+
+        output
     }
+}
+
+/// Every motion with the rope drawn after it, like the puzzle walkthrough,
+/// finishing with everywhere the tail went.
+pub fn trace(input: &str, knots: usize) -> String {
+    let mut rope = Rope::new(knots);
+    let mut output = String::new();
 
-    rope.tail_history.len()
+    for motion in parse(input) {
+        rope.apply(motion);
+        output.push_str(&format!("== {} ==\n\n{}\n", motion, rope.render(false)));
+    }
+    output.push_str(&format!("== Visited ==\n\n{}", rope.render(true)));
+
+    output
+}
+
+pub fn part_1(input: &str) -> usize {
+    part_2(input, 2)
 }
 
-pub fn part_2(input: &str, length: usize) -> usize {
-    let commands = parse(input);
-    let mut rope = Rope::new(length);
+pub fn part_2(input: &str, knots: usize) -> usize {
+    let mut rope = Rope::new(knots);
 
-    for command in commands {
-        rope.move_head(command);
+    for motion in parse(input) {
+        rope.apply(motion);
     }
 
-    rope.get_tail_history().len()
+    rope.tail_visited().len()
 }
 
-fn parse(input: &str) -> Vec<Direction> {
+pub fn parse(input: &str) -> Vec<Motion> {
     input
         .lines()
-        .into_iter()
-        .flat_map(|line| {
+        .map(|line| {
             let mut split_line = line.split_whitespace();
-            let direction = match split_line.next() {
-                Some("U") => Direction::Up,
-                Some("D") => Direction::Down,
-                Some("L") => Direction::Left,
-                Some("R") => Direction::Right,
-                _ => panic!("Unknown command!"),
-            };
+            let direction = split_line
+                .next()
+                .and_then(Direction::from_code)
+                .expect("Unknown command!");
 
-            let amount: usize = split_line.next().unwrap().parse().unwrap();
+            let steps: usize = split_line.next().unwrap().parse().unwrap();
 
-            std::iter::repeat(direction).take(amount)
+            Motion { direction, steps }
         })
         .collect()
 }
@@ -183,25 +340,142 @@ mod tests {
     fn test_rope() {
         let input = fs::read_to_string(INPUT).unwrap();
 
-        assert_eq!(part_2(&input, 1), 5874)
+        assert_eq!(part_2(&input, 2), 5874)
     }
 
     #[test]
     fn test_part_2() {
         let input = fs::read_to_string(TEST_INPUT).unwrap();
-        assert_eq!(part_2(&input, 9), 1);
+        assert_eq!(part_2(&input, 10), 1);
     }
 
     #[test]
     fn test_part_2_large_input() {
         let input = fs::read_to_string(TEST_INPUT_2).unwrap();
-        assert_eq!(part_2(&input, 9), 36);
+        assert_eq!(part_2(&input, 10), 36);
     }
 
     #[test]
     fn run_part_2() {
         let input = fs::read_to_string(INPUT).unwrap();
 
-        println!("{:?}", part_2(&input, 9))
+        println!("{:?}", part_2(&input, 10))
+    }
+
+    #[test]
+    fn check_rope() {
+        let input = fs::read_to_string(TEST_INPUT).unwrap();
+        let mut rope = Rope::new(10);
+        let motions = parse(&input);
+
+        rope.apply(motions[0]);
+        assert_eq!(rope.head(), (4, 0));
+        assert_eq!(rope.knot(3), (1, 0));
+        assert_eq!(rope.tail(), (0, 0));
+
+        rope.apply(motions[1]);
+        assert_eq!(rope.knots()[..5], [(4, 4), (4, 3), (4, 2), (3, 2), (2, 2)]);
+        assert_eq!(rope.knot(5), (1, 1));
+        assert_eq!(rope.visited(4).len(), 3);
+
+        for &motion in motions[2..].iter() {
+            rope.apply(motion);
+        }
+        assert_eq!(rope.tail_visited().len(), 1);
+        assert_eq!(rope.visited(1).len(), 13);
+    }
+
+    #[test]
+    fn check_render() {
+        let input = fs::read_to_string(TEST_INPUT).unwrap();
+        let motions = parse(&input);
+
+        let mut rope = Rope::new(2);
+        rope.apply(motions[0]);
+        assert_eq!(rope.render(false), "s..TH\n");
+
+        let mut rope = Rope::new(10);
+        rope.apply(motions[0]);
+        rope.apply(motions[1]);
+        assert_eq!(rope.render(false), "....H\n....1\n..432\n.5...\n6....\n");
+
+        let mut rope = Rope::new(2);
+        for &motion in motions.iter() {
+            rope.apply(motion);
+        }
+        assert_eq!(rope.render(true), "..##.\n...##\n.####\n....#\ns###.\n");
+    }
+
+    #[test]
+    fn check_trace() {
+        let input = fs::read_to_string(TEST_INPUT).unwrap();
+        let trace = trace(&input, 2);
+
+        assert!(trace.starts_with("== R 4 ==\n\ns..TH\n\n== U 4 ==\n\n"));
+        assert!(trace.ends_with("== Visited ==\n\n..##.\n...##\n.####\n....#\ns###.\n"));
+        assert_eq!(trace.matches("== ").count(), parse(&input).len() + 1);
+    }
+
+    #[test]
+    fn check_diagonal_motions() {
+        let motions = parse("UR 3\nDL 1\nDR 2");
+        assert_eq!(
+            motions[0],
+            Motion {
+                direction: Direction::UpRight,
+                steps: 3
+            }
+        );
+        assert_eq!(motions[2].to_string(), "DR 2");
+
+        let mut rope = Rope::new(3);
+        for motion in motions {
+            rope.apply(motion);
+        }
+        assert_eq!(rope.knots(), [(4, 0), (3, 1), (2, 1)]);
+        assert_eq!(rope.visited(2).len(), 3);
+    }
+
+    #[test]
+    fn check_follow_rules() {
+        let right = Motion {
+            direction: Direction::Right,
+            steps: 5,
+        };
+        let up = Motion {
+            direction: Direction::Up,
+            steps: 2,
+        };
+
+        let mut rope = Rope::new(2).with_rule(FollowRule::Orthogonal);
+        rope.apply(up);
+        rope.apply(right);
+        // Cuts the corner in two moves, rather than one diagonal.
+        assert_eq!(rope.tail(), (5, 1));
+        assert_eq!(rope.tail_visited().len(), 7);
+        assert!(rope.tail_visited().contains(&(1, 1)));
+
+        let mut rope = Rope::new(2).with_rule(FollowRule::Slack(3));
+        rope.apply(right);
+        assert_eq!(rope.tail(), (2, 0));
+        assert_eq!(rope.tail_visited().len(), 3);
+
+        let mut rope = Rope::new(2).with_rule(FollowRule::Elastic(3));
+        rope.apply(right);
+        assert_eq!(rope.tail(), (3, 0));
+        assert_eq!(rope.tail_visited().len(), 4);
+
+        // No slack at all drags it right on top of the head.
+        let mut rope = Rope::new(2).with_rule(FollowRule::Slack(0));
+        rope.apply(right);
+        assert_eq!(rope.tail(), rope.head());
+
+        // Slack of one is just the usual rule.
+        let input = fs::read_to_string(TEST_INPUT_2).unwrap();
+        let mut rope = Rope::new(10).with_rule(FollowRule::Slack(1));
+        for motion in parse(&input) {
+            rope.apply(motion);
+        }
+        assert_eq!(rope.tail_visited().len(), 36);
     }
 }
@@ -0,0 +1,177 @@
+use std::ops::Range;
+
+/// A set of integers, stored as sorted, disjoint, non-touching ranges.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntervalSet {
+    ranges: Vec<Range<i64>>,
+}
+
+impl IntervalSet {
+    pub fn new() -> Self {
+        IntervalSet { ranges: Vec::new() }
+    }
+
+    /// The ranges in order, none of them empty or touching.
+    pub fn ranges(&self) -> &[Range<i64>] {
+        &self.ranges
+    }
+
+    /// How many integers are in the set.
+    pub fn len(&self) -> u64 {
+        self.ranges.iter().map(|r| (r.end - r.start) as u64).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    pub fn contains(&self, n: i64) -> bool {
+        // First range that ends after n is the only one that could hold it.
+        let i = self.ranges.partition_point(|r| r.end <= n);
+        self.ranges.get(i).is_some_and(|r| r.start <= n)
+    }
+
+    /// Adds every integer in `range`.
+    pub fn insert(&mut self, range: Range<i64>) {
+        if range.is_empty() {
+            return;
+        }
+
+        // Everything from `first` to `last` overlaps or touches `range`.
+        let first = self.ranges.partition_point(|r| r.end < range.start);
+        let last = self.ranges.partition_point(|r| r.start <= range.end);
+
+        let (start, end) = if last > first {
+            (
+                self.ranges[first].start.min(range.start),
+                self.ranges[last - 1].end.max(range.end),
+            )
+        } else {
+            (range.start, range.end)
+        };
+
+        self.ranges.splice(first..last, std::iter::once(start..end));
+    }
+
+    /// Takes out every integer in `range`.
+    pub fn remove(&mut self, range: Range<i64>) {
+        if range.is_empty() {
+            return;
+        }
+
+        // Everything from `first` to `last` overlaps `range`.
+        let first = self.ranges.partition_point(|r| r.end <= range.start);
+        let last = self.ranges.partition_point(|r| r.start < range.end);
+        if first >= last {
+            return;
+        }
+
+        let before = self.ranges[first].start..range.start;
+        let after = range.end..self.ranges[last - 1].end;
+
+        self.ranges.splice(
+            first..last,
+            [before, after].into_iter().filter(|r| !r.is_empty()),
+        );
+    }
+
+    pub fn union(&self, other: &IntervalSet) -> IntervalSet {
+        self.ranges.iter().chain(&other.ranges).cloned().collect()
+    }
+
+    pub fn subtract(&self, other: &IntervalSet) -> IntervalSet {
+        let mut set = self.clone();
+        for range in other.ranges.iter() {
+            set.remove(range.clone());
+        }
+        set
+    }
+
+    /// Everything in `within` that isn't in the set.
+    pub fn gaps(&self, within: Range<i64>) -> IntervalSet {
+        let mut gaps = IntervalSet::new();
+        gaps.insert(within);
+        gaps.subtract(self)
+    }
+}
+
+impl FromIterator<Range<i64>> for IntervalSet {
+    // Sorting first means each range only has to be checked against the
+    // last one, rather than searched for.
+    fn from_iter<T: IntoIterator<Item = Range<i64>>>(iter: T) -> Self {
+        let mut ranges: Vec<Range<i64>> = iter.into_iter().filter(|r| !r.is_empty()).collect();
+        ranges.sort_by_key(|r| r.start);
+
+        let mut merged: Vec<Range<i64>> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+
+        IntervalSet { ranges: merged }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_insert() {
+        let mut set = IntervalSet::new();
+        set.insert(10..20);
+        set.insert(0..5);
+        set.insert(30..40);
+        assert_eq!(set.ranges(), &[0..5, 10..20, 30..40]);
+
+        set.insert(5..10);
+        assert_eq!(set.ranges(), &[0..20, 30..40]);
+
+        set.insert(15..35);
+        assert_eq!(set.ranges(), std::slice::from_ref(&(0..40)));
+        assert_eq!(set.len(), 40);
+    }
+
+    #[test]
+    fn check_remove() {
+        let mut set: IntervalSet = [0..10, 20..30].into_iter().collect();
+
+        set.remove(5..25);
+        assert_eq!(set.ranges(), &[0..5, 25..30]);
+
+        set.remove(0..1);
+        set.remove(29..100);
+        assert_eq!(set.ranges(), &[1..5, 25..29]);
+
+        set.remove(2..3);
+        assert_eq!(set.ranges(), &[1..2, 3..5, 25..29]);
+        assert_eq!(set.len(), 7);
+    }
+
+    #[test]
+    fn check_from_iter() {
+        let set: IntervalSet = [12..13, 2..14, 14..15, -2..3, 16..20, 18..19, 5..5]
+            .into_iter()
+            .collect();
+
+        assert_eq!(set.ranges(), &[-2..15, 16..20]);
+        assert!(set.contains(-2));
+        assert!(set.contains(14));
+        assert!(!set.contains(15));
+        assert!(set.contains(19));
+        assert!(!set.contains(20));
+    }
+
+    #[test]
+    fn check_gaps() {
+        let set: IntervalSet = [0..5, 8..10, 15..30].into_iter().collect();
+        let mut other = IntervalSet::new();
+        other.insert(4..9);
+
+        assert_eq!(set.gaps(-5..20).ranges(), &[-5..0, 5..8, 10..15]);
+        assert_eq!(set.union(&other).ranges(), &[0..10, 15..30]);
+        assert_eq!(set.subtract(&other).ranges(), &[0..4, 9..10, 15..30]);
+    }
+}
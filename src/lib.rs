@@ -14,3 +14,4 @@ pub mod d6_tuning_trouble;
 pub mod d7_no_space_left;
 pub mod d8_treetop_tree_house;
 pub mod d9_rope_bridge;
+pub mod interval_set;